@@ -343,6 +343,7 @@ func (p *Program) RollbackTransaction() error {
 }
 
 // ApplyUpdates applies updates to DDlog tables. Must be called as part of a transaction.
+// On failure, the transaction is rolled back (see ddlog_apply_updates in ddlog.h).
 func (p *Program) ApplyUpdates(commands ...Command) error {
 	cmdArray := C.makeCmdArray(C.size_t(len(commands)))
 	defer C.freeCmdArray(cmdArray)
@@ -357,6 +358,7 @@ func (p *Program) ApplyUpdates(commands ...Command) error {
 }
 
 // ApplyUpdate applies a single update to DDlog tables. Must be called as part of a transaction.
+// On failure, the transaction is rolled back (see ddlog_apply_updates in ddlog.h).
 func (p *Program) ApplyUpdate(command Command) error {
 	rc := C.ddlog_apply_updates(p.ptr, &command.ptr, 1)
 	if rc != 0 {
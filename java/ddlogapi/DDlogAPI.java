@@ -528,6 +528,10 @@ public class DDlogAPI {
     /**
      * Apply updates to DDlog input relations.
      *
+     * If any of the updates fails, the whole transaction is rolled back,
+     * including updates applied by earlier calls in the same transaction,
+     * and no transaction is in progress when this method throws.
+     *
      * See <code>ddlog.h: ddlog_apply_updates()</code>
      */
     public void applyUpdates(DDlogRecCommand[] commands) throws DDlogException {
@@ -43,7 +43,8 @@ public class XTest {
             builder.insert_R0(10, 13);
             this.api.transactionStart();
             this.expectFail("duplicate key", () -> builder.applyUpdates(this.api) );
-            this.api.transactionCommitDumpChanges(this::onCommit);
+            // The failed update rolled back the transaction.
+            this.expectFail("no transaction in progress", () -> this.api.transactionCommitDumpChanges(this::onCommit) );
         }
         {
             xUpdateBuilder builder = new xUpdateBuilder();
@@ -64,7 +64,8 @@ public class XTest {
             builder.insert_R0(10, 13);
             this.api.transactionStart();
             this.expectFail("duplicate key", () -> builder.applyUpdates(this.api) );
-            this.api.transactionCommitDumpChanges(this::onCommit);
+            // The failed update rolled back the transaction.
+            this.expectFail("no transaction in progress", () -> this.api.transactionCommitDumpChanges(this::onCommit) );
         }
         {
             // Insert the same key twice in separate transactions
@@ -79,7 +80,8 @@ public class XTest {
             builder1.insert_R0(10, 12);
             this.api.transactionStart();
             this.expectFail("duplicate key", () -> builder1.applyUpdates(this.api) );
-            this.api.transactionCommitDumpChanges(this::onCommit);
+            // The failed update rolled back the transaction.
+            this.expectFail("no transaction in progress", () -> this.api.transactionCommitDumpChanges(this::onCommit) );
         }
         {
             // Remove missing key
@@ -87,7 +89,8 @@ public class XTest {
             builder.delete_R0(0, 12);
             this.api.transactionStart();
             this.expectFail("key not found", () -> builder.applyUpdates(this.api) );
-            this.api.transactionCommitDumpChanges(this::onCommit);
+            // The failed update rolled back the transaction.
+            this.expectFail("no transaction in progress", () -> this.api.transactionCommitDumpChanges(this::onCommit) );
         }
         {
             // Buggy DDlogRecCommand
@@ -97,7 +100,8 @@ public class XTest {
             commands[0] = command;
             this.api.transactionStart();
             this.expectFail("not a struct", () -> this.api.applyUpdates(commands));
-            this.api.transactionCommitDumpChanges(this::onCommit);
+            // The failed update rolled back the transaction.
+            this.expectFail("no transaction in progress", () -> this.api.transactionCommitDumpChanges(this::onCommit) );
         }
         {
             // Buggy table id
@@ -107,7 +111,8 @@ public class XTest {
             commands[0] = command;
             this.api.transactionStart();
             this.expectFail("Unknown relation", () -> this.api.applyUpdates(commands));
-            this.api.transactionCommitDumpChanges(this::onCommit);
+            // The failed update rolled back the transaction.
+            this.expectFail("no transaction in progress", () -> this.api.transactionCommitDumpChanges(this::onCommit) );
         }
 
         // Two stops in a row
@@ -468,12 +468,15 @@ extern int ddlog_transaction_rollback(ddlog_prog hprog);
  * the `upds` array (but not the array itself), so they can no longer be
  * accessed by the caller after the function returns.
  *
- * This function fails non-atomically: upon a failure, it may have applied
- * a _subset_ of input commands (one exception is when `ddlog_apply_updates`
- * is called with a single command, in which case it leaves the database
- * unmodified in case of a failure).  Use `ddlog_transaction_rollback()` to
- * bring the database back to a known state, specifically the state where it
- * was before the start of the transaction.
+ * Upon a failure, this function rolls back the current transaction,
+ * discarding all updates applied since the start of the transaction,
+ * including updates applied by earlier calls to `ddlog_apply_updates`.
+ * The database is left in the state where it was before the start of the
+ * transaction and no transaction is in progress: the caller must not call
+ * `ddlog_transaction_rollback()` or `ddlog_transaction_commit()`, which
+ * will fail, and must call `ddlog_transaction_start()` before applying
+ * further updates.  The error message identifies the failed command by its
+ * index in `upds`.
  */
 extern int ddlog_apply_updates(ddlog_prog prog, ddlog_cmd **upds, size_t n);
 
@@ -488,6 +491,8 @@ extern int ddlog_apply_updates(ddlog_prog prog, ddlog_cmd **upds, size_t n);
  * On success, returns `0`. On error, returns a negative value and
  * writes error message (see `print_err_msg` parameter to `ddlog_run()`).
  *
+ * Like `ddlog_apply_updates()`, upon a failure, including a failure to
+ * decode the FlatBuffer, this function rolls back the current transaction.
  */
 extern int ddlog_apply_updates_from_flatbuf(ddlog_prog prog,
                                             const unsigned char *buf,
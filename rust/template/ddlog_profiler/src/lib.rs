@@ -99,6 +99,18 @@ pub struct CpuProfileRecord {
 /// Serialized representation of a CPU profile.
 pub type CpuProfile = Vec<CpuProfileRecord>;
 
+/// CPU usage of all operators associated with a source code location,
+/// typically a rule or a part of a rule.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RuleProfile {
+    /// Source code location.
+    pub location: SourcePosition,
+    /// Total CPU time spent in operators associated with the location.
+    pub cpu_time: Duration,
+    /// Total number of invocations of these operators.
+    pub invocations: usize,
+}
+
 /// Serialized representation of an arrangement size profile record.
 #[derive(Serialize)]
 pub struct SizeProfileRecord {
@@ -260,6 +272,41 @@ impl Profile {
         Some(cpu_profile)
     }
 
+    /// Aggregates the CPU profile by source code location, in the order of
+    /// decreasing CPU time.  An operator associated with multiple locations
+    /// (e.g., a join) counts towards each of them; operators with unknown
+    /// location are skipped.
+    pub fn rule_profile(&self) -> Option<Vec<RuleProfile>> {
+        let mut rules: HashMap<&SourcePosition, (Duration, usize)> = HashMap::new();
+        for (opid, (duration, invocations)) in self.durations.iter() {
+            let locations = self
+                .debug_info
+                .get(opid)
+                .map(|debug_info| debug_info.source_pos())
+                .unwrap_or_default();
+            for location in locations.iter().filter(|p| !p.is_unknown()) {
+                let (total, ncalls) = rules.entry(location).or_default();
+                *total += *duration;
+                *ncalls += *invocations;
+            }
+        }
+
+        if rules.is_empty() {
+            return None;
+        }
+
+        let mut rule_profile: Vec<RuleProfile> = rules
+            .into_iter()
+            .map(|(location, (cpu_time, invocations))| RuleProfile {
+                location: location.clone(),
+                cpu_time,
+                invocations,
+            })
+            .collect();
+        rule_profile.sort_by(|rule1, rule2| rule1.cpu_time.cmp(&rule2.cpu_time).reverse());
+        Some(rule_profile)
+    }
+
     fn size_profile(&self, sizes: &FnvHashMap<usize, isize>) -> Vec<SizeProfileRecord> {
         let mut size_vec: Vec<(usize, isize)> = sizes.clone().into_iter().collect();
         size_vec.sort_by(|(_, sz1), (_, sz2)| sz1.cmp(sz2).reverse());
@@ -619,3 +666,73 @@ impl Profile {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SOURCE_CODE: DDlogSourceCode = DDlogSourceCode { code: &[] };
+
+    type Event = ((Duration, usize, TimelyEvent), Option<OperatorDebugInfo>);
+
+    fn operates(id: usize, source_pos: SourcePosition) -> Event {
+        let event = TimelyEvent::Operates(OperatesEvent {
+            id,
+            addr: vec![0, id],
+            name: "Map".to_string(),
+        });
+        (
+            (Duration::from_millis(0), 0, event),
+            Some(OperatorDebugInfo::map(source_pos)),
+        )
+    }
+
+    fn schedule(id: usize, start_stop: StartStop, millis: u64) -> Event {
+        let event = TimelyEvent::Schedule(ScheduleEvent { id, start_stop });
+        ((Duration::from_millis(millis), 0, event), None)
+    }
+
+    #[test]
+    fn rule_profile() {
+        let rule1 = SourcePosition::new_location("test.dl", 1, 1);
+        let rule2 = SourcePosition::new_location("test.dl", 2, 1);
+
+        let mut profile = Profile::new(&SOURCE_CODE, PathBuf::from("."));
+        assert_eq!(profile.rule_profile(), None);
+
+        profile.update(&ProfMsg::TimelyMessage(
+            vec![
+                operates(1, rule1.clone()),
+                operates(2, rule2.clone()),
+                operates(3, rule2.clone()),
+                operates(4, SourcePosition::Unknown),
+                schedule(1, StartStop::Start, 0),
+                schedule(1, StartStop::Stop, 5),
+                schedule(2, StartStop::Start, 5),
+                schedule(2, StartStop::Stop, 10),
+                schedule(3, StartStop::Start, 10),
+                schedule(3, StartStop::Stop, 12),
+                schedule(4, StartStop::Start, 12),
+                schedule(4, StartStop::Stop, 20),
+            ],
+            true,
+            false,
+        ));
+
+        assert_eq!(
+            profile.rule_profile(),
+            Some(vec![
+                RuleProfile {
+                    location: rule2,
+                    cpu_time: Duration::from_millis(7),
+                    invocations: 2,
+                },
+                RuleProfile {
+                    location: rule1,
+                    cpu_time: Duration::from_millis(5),
+                    invocations: 1,
+                },
+            ])
+        );
+    }
+}
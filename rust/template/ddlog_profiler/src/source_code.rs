@@ -8,7 +8,7 @@ use std::{
 
 /// Location of a single character of a range of characters
 /// in the source of the program.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub enum SourcePosition {
     /// Contiguous code snippet:
     /// `sourcefile:line1.column1-line2.column2`.
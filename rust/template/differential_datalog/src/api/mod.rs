@@ -1,10 +1,13 @@
 mod c_api;
+mod shadow;
 pub mod update_handler;
 
 #[cfg(feature = "c_api")]
 pub use c_api::*;
+pub use shadow::{Divergence, DivergenceCallback};
 
 use crate::flatbuf::FlatbufConverter;
+use crate::histogram::{Histogram, HistogramSnapshot};
 use crate::{
     api::update_handler::{
         ChainedUpdateHandler, DeltaUpdateHandler, IMTUpdateHandler, ThreadUpdateHandler,
@@ -12,21 +15,31 @@ use crate::{
     },
     ddlog::D3logLocalizer,
     ddval::DDValue,
-    program::{config::Config, IdxId, Program, RelId, RelationCallback, RunningProgram, Update},
-    record::{IntoRecord, Record, UpdCmd},
+    program::{
+        config::Config, ArrId, IdxId, Program, RelId, RelationCallback, RunningProgram, Update,
+    },
+    record::{IntoRecord, Name, Record, RelIdentifier, UpdCmd},
     replay, AnyDeserialize, CommandRecorder, D3log, D3logLocationId, DDlog, DDlogDump,
-    DDlogDynamic, DDlogInventory, DDlogProfiling, DeltaMap,
+    DDlogDynamic, DDlogInventory, DDlogProfiling, DeltaMap, MerkleTree,
 };
-use ddlog_profiler::{CpuProfile, DDlogSourceCode, SizeProfileRecord};
+use ddlog_profiler::{CpuProfile, DDlogSourceCode, RuleProfile, SizeProfileRecord};
+use shadow::Shadow;
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashMap},
     ffi::CString,
     fmt,
-    fs::File,
-    io::{self, Write},
+    fs::{self, File},
+    io::{self, BufWriter, Write},
     mem,
     os::raw::c_char,
-    sync::{Arc, Mutex},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
 };
 
 type BoxedInventory = Box<dyn DDlogInventory + Send + Sync + 'static>;
@@ -48,6 +61,29 @@ pub struct HDDlog {
     /// When set, all commands sent to the program are recorded in
     /// the specified `.dat` file so that they can be replayed later.
     pub command_recorder: Option<CommandRecorder<File, BoxedInventory>>,
+    /// Held for writing for the duration of each commit and for reading by
+    /// `read_tx`, so that read transactions never observe a partially
+    /// applied commit.
+    commit_lock: RwLock<()>,
+    /// Signalled, together with `prog`, whenever a transaction ends or a
+    /// checkpoint completes.
+    transaction_done: Condvar,
+    /// Number of `checkpoint` calls waiting for the current transaction to
+    /// end.  New transactions are not started while it is non-zero.
+    checkpoints_pending: AtomicUsize,
+    /// Set by `stop`, so that checkpoints no longer wait for a transaction
+    /// that can never finish.
+    stopped: AtomicBool,
+    /// Time spent in each successful commit.
+    commit_latency: Histogram,
+    /// Maps old relation names to new ones; applied to commands by
+    /// `convert_update_command`.
+    relation_renames: HashMap<Name, Name>,
+    /// Time when the program finished its initial transaction.
+    start_time: Instant,
+    /// Candidate program that all transactions are also applied to; set by
+    /// `enable_shadow`.
+    shadow: Option<Shadow>,
 }
 
 /* Internals */
@@ -111,6 +147,14 @@ impl HDDlog {
             any_deserialize,
             flatbuf_converter,
             command_recorder: None,
+            commit_lock: RwLock::new(()),
+            transaction_done: Condvar::new(),
+            checkpoints_pending: AtomicUsize::new(0),
+            stopped: AtomicBool::new(false),
+            commit_latency: Histogram::default(),
+            relation_renames: HashMap::new(),
+            start_time: Instant::now(),
+            shadow: None,
         };
 
         Ok((program, init_state))
@@ -141,14 +185,68 @@ impl HDDlog {
         }
     }
 
+    /// Starts shadow evaluation: from now on, all updates and transactions
+    /// applied to this program are also applied to `candidate`, e.g., a new
+    /// version of the program, with updates translated to the candidate's
+    /// relation with the same name.  Whenever a transaction commits, the
+    /// changes it made to each relation of the two programs are compared by
+    /// relation name, and `on_divergence` is invoked for each relation on
+    /// which they differ.
+    ///
+    /// The candidate never affects this program: its errors are reported via
+    /// `eprintln` and ignored.  `candidate` should not have a transaction in
+    /// progress.  Replaces the previous candidate, if any.
+    pub fn enable_shadow(&mut self, candidate: HDDlog, on_divergence: DivergenceCallback) {
+        self.shadow = Some(Shadow::new(candidate, on_divergence));
+    }
+
+    /// Stops shadow evaluation and returns the candidate program.
+    pub fn disable_shadow(&mut self) -> Option<HDDlog> {
+        self.shadow.take().map(|shadow| shadow.candidate)
+    }
+
+    /// Sets the relation renames applied to update commands before they are
+    /// converted to updates (see `UpdCmd::rename_relation`), so that
+    /// commands that refer to a relation by an old name, e.g., commands
+    /// replayed from a recording made before the relation was renamed, are
+    /// applied to the relation's new name.  Replaces any previously set
+    /// renames.
+    pub fn set_relation_renames(&mut self, renames: HashMap<Name, Name>) {
+        self.relation_renames = renames;
+    }
+
+    /// Relation renames set with `set_relation_renames`.
+    pub fn relation_renames(&self) -> &HashMap<Name, Name> {
+        &self.relation_renames
+    }
+
     /// Apply a set of updates directly from the flatbuffer
     /// representation
     #[cfg_attr(feature = "flatbuf", doc(hidden))]
     pub fn apply_updates_from_flatbuf(&self, buf: &[u8]) -> Result<(), String> {
-        let updates = self.flatbuf_converter.updates_from_buffer(buf)?;
+        let updates = self
+            .flatbuf_converter
+            .updates_from_buffer(buf)
+            .map_err(|e| self.rollback_after_error(e))?;
         self.apply_updates(&mut updates.into_iter())
     }
 
+    /// Rolls back the current transaction after updates failed to convert,
+    /// as `apply_updates` does for updates it rejects, and returns `error`
+    /// annotated with the outcome of the rollback.
+    fn rollback_after_error(&self, error: String) -> String {
+        let rollback_res = self.prog.lock().unwrap().transaction_rollback();
+        self.transaction_done.notify_all();
+        self.shadow_command(|shadow| shadow.abort());
+        match rollback_res {
+            Ok(()) => format!("{} (transaction rolled back)", error),
+            Err(rollback_err) => format!(
+                "{} (failed to roll back transaction: {})",
+                error, rollback_err
+            ),
+        }
+    }
+
     /// Similar to `query_index`, but extracts query from a flatbuffer.
     #[cfg_attr(feature = "flatbuf", doc(hidden))]
     pub fn query_index_from_flatbuf(&self, buf: &[u8]) -> Result<BTreeSet<DDValue>, String> {
@@ -156,6 +254,238 @@ impl HDDlog {
         self.query_index(index_id, key)
     }
 
+    /// Returns an order-independent digest of the current contents of an
+    /// output relation (see `DeltaMap::rel_digest`).  Two instances of the
+    /// same program whose copies of `table` hold identical values return the
+    /// same digest.
+    ///
+    /// Like `dump_table`, this requires the program to be started with the
+    /// `do_store` flag set.
+    pub fn relation_digest(&self, table: RelId) -> Result<u64, String> {
+        if let Some(ref db) = self.db {
+            Ok(db.lock().unwrap().rel_digest(table))
+        } else {
+            Err(
+                "cannot compute digest: ddlog_run() was invoked with do_store flag set to false"
+                    .to_string(),
+            )
+        }
+    }
+
+    /// Builds a Merkle tree over the current contents of an output relation
+    /// (see `DeltaMap::rel_merkle`).  Two instances can find the values on
+    /// which their copies of `table` disagree by exchanging tree nodes top
+    /// down, rather than the full contents of the relation.  Both must use
+    /// the same `fanout` and `depth`.
+    ///
+    /// Like `dump_table`, this requires the program to be started with the
+    /// `do_store` flag set.
+    pub fn relation_merkle(
+        &self,
+        table: RelId,
+        fanout: usize,
+        depth: usize,
+    ) -> Result<MerkleTree, String> {
+        if let Some(ref db) = self.db {
+            db.lock().unwrap().rel_merkle(table, fanout, depth)
+        } else {
+            Err(
+                "cannot build Merkle tree: ddlog_run() was invoked with do_store flag set to false"
+                    .to_string(),
+            )
+        }
+    }
+
+    /// Like `dump_table`, but only passes the fields listed in `projection`
+    /// to `cb` (see `Record::project_struct_fields`).  Values that are not
+    /// named structs are passed unchanged.  `None` selects all fields.
+    ///
+    /// Projection happens after each value has been converted to a `Record`,
+    /// so this reduces the amount of data handed to `cb`, but not the cost
+    /// of the conversion.  Fails if `projection` names a field that does not
+    /// exist in a value of the relation; `cb` is not invoked for that value
+    /// or any value after it.
+    pub fn dump_table_projected(
+        &self,
+        table: RelId,
+        projection: Option<&[&str]>,
+        cb: Option<&dyn Fn(&Record, isize) -> bool>,
+    ) -> Result<(), String> {
+        let (projection, cb) = match (projection, cb) {
+            (Some(projection), Some(cb)) => (projection, cb),
+            (_, cb) => return self.dump_table(table, cb),
+        };
+
+        let error = RefCell::new(None);
+        self.dump_table(
+            table,
+            Some(
+                &|record: &Record, weight| match record.project_struct_fields(projection) {
+                    Ok(Some(projected)) => cb(&projected, weight),
+                    Ok(None) => cb(record, weight),
+                    Err(e) => {
+                        *error.borrow_mut() = Some(e);
+                        false
+                    }
+                },
+            ),
+        )?;
+
+        error.into_inner().map_or(Ok(()), Err)
+    }
+
+    /// Like `DDlog::transaction_commit_dump_changes`, but also reports how
+    /// much work the transaction caused.
+    pub fn transaction_commit_dump_changes_with_stats(
+        &self,
+    ) -> Result<(DeltaMap<DDValue>, EvalStats), String> {
+        let (delta, commit_duration) = self.do_transaction_commit_dump_changes(true)?;
+
+        let stats = EvalStats {
+            changes: delta.values().map(|rel| rel.len()).sum(),
+            relations_touched: delta
+                .iter()
+                .filter(|(_, rel)| !rel.is_empty())
+                .map(|(relid, _)| *relid)
+                .collect(),
+            commit_duration,
+        };
+
+        Ok((delta, stats))
+    }
+
+    /// Returns a snapshot of the distribution of commit latencies, i.e., the
+    /// time it took to commit each transaction and propagate its changes
+    /// through the dataflow.  Only successful commits are counted.
+    pub fn commit_latency(&self) -> HistogramSnapshot {
+        self.commit_latency.snapshot()
+    }
+
+    /// Like `DDlogDynamic::stop`, but also returns a summary of the session.
+    pub fn stop_with_report(&self) -> Result<ShutdownReport, String> {
+        self.shadow_command(|shadow| shadow.candidate.stop());
+        let mut prog = self.prog.lock().unwrap();
+        let res = prog.stop();
+        self.stopped.store(true, Ordering::SeqCst);
+        self.transaction_done.notify_all();
+        res?;
+
+        let relation_sizes = match self.db {
+            Some(ref db) => db
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(relid, rel)| {
+                    let name = self
+                        .inventory
+                        .get_table_name(*relid)
+                        .map_or_else(|_| relid.to_string(), |name| name.to_string());
+                    (name, rel.len())
+                })
+                .collect(),
+            None => BTreeMap::new(),
+        };
+
+        Ok(ShutdownReport {
+            transactions_committed: prog.commit_count(),
+            uptime: self.start_time.elapsed(),
+            relation_sizes,
+        })
+    }
+
+    /// Atomically writes a snapshot of all input relations to `path` and
+    /// returns the version of the snapshot, i.e., the number of transactions
+    /// committed before it was taken (see `RunningProgram::commit_count`).
+    ///
+    /// The file contains a comment line with the version followed by a
+    /// transaction that inserts the snapshot, in the format of
+    /// `dump_input_snapshot`, so it can be replayed into a new instance of
+    /// the program.  If a transaction is in progress, waits for it to be
+    /// committed or rolled back, and delays new transactions until the
+    /// snapshot has been taken, so that the snapshot always reflects exactly
+    /// one committed version.  The snapshot is written to a temporary file
+    /// that is then renamed to `path`, so `path` never contains a partial
+    /// snapshot.
+    ///
+    /// Must not be called by a thread that has a transaction in progress, as
+    /// that transaction could never finish.  Fails if the program is stopped
+    /// while a transaction is in progress.
+    pub fn checkpoint(&self, path: &Path) -> Result<u64, String> {
+        // Concurrent checkpoints to the same path each use their own
+        // temporary file.
+        static TMP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(format!(
+            ".{}.{}.tmp",
+            process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::SeqCst)
+        ));
+        let tmp_path = PathBuf::from(tmp_path);
+
+        self.checkpoints_pending.fetch_add(1, Ordering::SeqCst);
+        let res = {
+            let prog = self
+                .transaction_done
+                .wait_while(self.prog.lock().unwrap(), |prog| {
+                    prog.transaction_in_progress() && !self.stopped.load(Ordering::SeqCst)
+                })
+                .unwrap();
+            let version = prog.commit_count();
+
+            let write = || -> io::Result<()> {
+                let mut writer = BufWriter::new(File::create(&tmp_path)?);
+                writeln!(writer, "# checkpoint version {}", version)?;
+                writeln!(writer, "start;")?;
+                self.write_input_snapshot(&prog, &mut writer, ";\n")?;
+                writeln!(writer, "commit;")?;
+                writer.into_inner()?.sync_all()
+            };
+            if prog.transaction_in_progress() {
+                Err("program stopped with a transaction in progress".to_string())
+            } else {
+                write().map(|()| version).map_err(|e| {
+                    // Do not leave a partial snapshot behind.
+                    let _ = fs::remove_file(&tmp_path);
+                    format!("failed to write checkpoint {}: {}", tmp_path.display(), e)
+                })
+            }
+        };
+        self.checkpoints_pending.fetch_sub(1, Ordering::SeqCst);
+        self.transaction_done.notify_all();
+        let version = res?;
+
+        fs::rename(&tmp_path, path).map_err(|e| {
+            format!(
+                "failed to rename {} to {}: {}",
+                tmp_path.display(),
+                path.display(),
+                e
+            )
+        })?;
+        sync_parent_dir(path)
+            .map_err(|e| format!("failed to sync directory of {}: {}", path.display(), e))?;
+
+        Ok(version)
+    }
+
+    /// Runs `f` with a consistent read-only view of the program.
+    ///
+    /// Commits are blocked while `f` runs, so all reads performed through
+    /// the `ReadTx` observe the same committed state.  Other threads may
+    /// still start transactions and apply updates in the meantime, but
+    /// those updates only become visible once they are committed, i.e.,
+    /// after `f` returns.  This is the read-side counterpart of
+    /// `transaction_start`/`transaction_commit`.
+    ///
+    /// Committing a transaction from inside `f` deadlocks.
+    pub fn read_tx<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&ReadTx<'_>) -> R,
+    {
+        let _commit_guard = self.commit_lock.read().unwrap();
+        f(&ReadTx { hddlog: self })
+    }
+
     fn db_dump_table<F>(db: &mut DeltaMap<DDValue>, table: usize, cb: Option<F>)
     where
         F: Fn(&Record, isize) -> bool,
@@ -169,6 +499,17 @@ impl HDDlog {
         };
     }
 
+    fn shadow_command<T, F>(&self, cmd: F)
+    where
+        F: FnOnce(&Shadow) -> Result<T, String>,
+    {
+        if let Some(ref shadow) = self.shadow {
+            let _ = cmd(shadow).map_err(|e| {
+                self.eprintln(&format!("shadow evaluation: candidate failed: {}", e));
+            });
+        }
+    }
+
     fn record_command<T, F>(&self, cmd: F)
     where
         F: FnOnce(
@@ -186,65 +527,206 @@ impl HDDlog {
     }
 
     pub fn convert_update_command(&self, command: &UpdCmd) -> Result<Update<DDValue>, String> {
-        command.to_update(&self.inventory)
+        match command.relation() {
+            RelIdentifier::RelName(rname) if self.relation_renames.contains_key(rname) => {
+                let mut command = command.clone();
+                command.rename_relation(&self.relation_renames);
+                command.to_update(&self.inventory)
+            }
+            _ => command.to_update(&self.inventory),
+        }
     }
-}
 
-impl DDlogDump for HDDlog {
-    fn dump_input_snapshot(&self, writer: &mut dyn Write) -> io::Result<()> {
-        let prog = self.prog.lock().unwrap();
+    /// Implements `dump_input_snapshot` for a program locked by the caller.
+    /// Commands are separated by ",\n" and the last one is followed by
+    /// `terminator`.
+    fn write_input_snapshot(
+        &self,
+        prog: &RunningProgram,
+        writer: &mut dyn Write,
+        terminator: &str,
+    ) -> io::Result<()> {
+        let mut separator = "";
+        let mut write_command = |writer: &mut dyn Write,
+                                 insert: bool,
+                                 relation_name: &str,
+                                 v: &DDValue|
+         -> io::Result<()> {
+            write!(writer, "{}", separator)?;
+            separator = ",\n";
+            if insert {
+                replay::record_insert(writer, relation_name, v)
+            } else {
+                replay::record_delete(writer, relation_name, v)
+            }
+        };
 
         for (&relation_id, &relation_name) in self.inventory.input_relation_ids() {
             match prog.get_input_relation_data(relation_id) {
                 Ok(valset) => {
                     for v in valset.iter() {
-                        replay::record_insert(writer, relation_name, v)?;
-                        writeln!(writer, ",")?;
+                        write_command(writer, true, relation_name, v)?;
                     }
                 }
 
                 Err(_) => match prog.get_input_relation_index(relation_id) {
                     Ok(ivalset) => {
                         for v in ivalset.values() {
-                            replay::record_insert(writer, relation_name, v)?;
-                            writeln!(writer, ",")?;
+                            write_command(writer, true, relation_name, v)?;
                         }
                     }
 
                     Err(_) => match prog.get_input_multiset_data(relation_id) {
                         Ok(ivalmset) => {
                             for (v, weight) in ivalmset.iter() {
-                                if *weight >= 0 {
-                                    for _ in 0..*weight {
-                                        replay::record_insert(writer, relation_name, v)?;
-                                        writeln!(writer, ",")?;
-                                    }
-                                } else {
-                                    for _ in 0..(-*weight) {
-                                        replay::record_delete(writer, relation_name, v)?;
-                                        writeln!(writer, ",")?;
-                                    }
+                                for _ in 0..weight.abs() {
+                                    write_command(writer, *weight >= 0, relation_name, v)?;
                                 }
                             }
                         }
 
                         Err(_) => {
-                            // Drop the lock before we panic just to try and avoid poisoning
-                            drop(prog);
-
-                            panic!(
-                                "Unknown input relation {:?} in dump_input_snapshot",
-                                relation_id,
-                            );
+                            return Err(io::Error::new(
+                                io::ErrorKind::Other,
+                                format!(
+                                    "unknown input relation {:?} in dump_input_snapshot",
+                                    relation_id
+                                ),
+                            ));
                         }
                     },
                 },
             }
         }
 
+        if !separator.is_empty() {
+            write!(writer, "{}", terminator)?;
+        }
         Ok(())
     }
 
+    fn index_to_arrangement_id(&self, index: IdxId) -> Result<ArrId, String> {
+        self.inventory
+            .index_to_arrangement_id(index)
+            .ok_or_else(|| format!("unknown index {}", index))
+    }
+}
+
+/// Flushes the directory entry of `path` to disk, which makes a preceding
+/// rename to `path` durable.
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(parent)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Statistics about a transaction commit, returned by
+/// `HDDlog::transaction_commit_dump_changes_with_stats`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvalStats {
+    /// Number of changes to output relations, i.e., the number of
+    /// `(value, weight)` pairs in the delta.
+    pub changes: usize,
+    /// Relations that have changed in the transaction.
+    pub relations_touched: BTreeSet<RelId>,
+    /// Time spent committing the transaction, including propagating changes
+    /// through the dataflow, but not waiting for other commits to finish.
+    /// This is the latency recorded in `HDDlog::commit_latency`.
+    pub commit_duration: Duration,
+}
+
+/// Summary of a session, returned by `HDDlog::stop_with_report`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShutdownReport {
+    /// Number of transactions committed, not counting the initial
+    /// transaction.
+    pub transactions_committed: u64,
+    /// Time elapsed between the end of the initial transaction and the
+    /// shutdown.
+    pub uptime: Duration,
+    /// Final number of values in each stored relation, by relation name.
+    /// Empty unless the program was started with the `do_store` flag set.
+    pub relation_sizes: BTreeMap<String, usize>,
+}
+
+/// A consistent read-only view of a running program, obtained from
+/// `HDDlog::read_tx`.
+///
+/// No transaction can commit while a `ReadTx` is alive, so all reads
+/// performed through it observe the same state.  The program itself is
+/// only locked for the duration of each individual read.
+pub struct ReadTx<'a> {
+    hddlog: &'a HDDlog,
+}
+
+impl<'a> ReadTx<'a> {
+    /// Enumerates the contents of an output relation.  See
+    /// `DDlogDump::dump_table`.
+    pub fn dump_table(
+        &self,
+        table: RelId,
+        cb: Option<&dyn Fn(&Record, isize) -> bool>,
+    ) -> Result<(), String> {
+        self.hddlog.dump_table(table, cb)
+    }
+
+    /// Computes the digest of an output relation.  See
+    /// `HDDlog::relation_digest`.
+    pub fn relation_digest(&self, table: RelId) -> Result<u64, String> {
+        self.hddlog.relation_digest(table)
+    }
+
+    /// Builds a Merkle tree over an output relation.  See
+    /// `HDDlog::relation_merkle`.
+    pub fn relation_merkle(
+        &self,
+        table: RelId,
+        fanout: usize,
+        depth: usize,
+    ) -> Result<MerkleTree, String> {
+        self.hddlog.relation_merkle(table, fanout, depth)
+    }
+
+    /// Returns all values associated with `key` in an index.  See
+    /// `DDlog::query_index`.
+    pub fn query_index(&self, index: IdxId, key: DDValue) -> Result<BTreeSet<DDValue>, String> {
+        self.hddlog
+            .record_command(|r| r.query_index(index, key.clone()));
+        let arrangement_id = self.hddlog.index_to_arrangement_id(index)?;
+
+        self.hddlog
+            .prog
+            .lock()
+            .unwrap()
+            .query_arrangement(arrangement_id, key)
+    }
+
+    /// Returns all values in an index.  See `DDlog::dump_index`.
+    pub fn dump_index(&self, index: IdxId) -> Result<BTreeSet<DDValue>, String> {
+        self.hddlog.record_command(|r| r.dump_index(index));
+        let arrangement_id = self.hddlog.index_to_arrangement_id(index)?;
+
+        self.hddlog
+            .prog
+            .lock()
+            .unwrap()
+            .dump_arrangement(arrangement_id)
+    }
+}
+
+impl DDlogDump for HDDlog {
+    fn dump_input_snapshot(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.write_input_snapshot(&self.prog.lock().unwrap(), writer, ",\n")
+    }
+
     fn dump_table(
         &self,
         table: RelId,
@@ -322,6 +804,16 @@ impl DDlogProfiling for HDDlog {
         }
     }
 
+    fn rule_profile(&self) -> Result<Option<Vec<RuleProfile>>, String> {
+        self.record_command(|r| r.rule_profile());
+        let rprog = self.prog.lock().unwrap();
+        if let Some(profile) = &rprog.profile {
+            Ok(profile.lock().unwrap().rule_profile())
+        } else {
+            Err("DDlog instance was created with self-profiler disabled".to_string())
+        }
+    }
+
     fn dump_profile(&self, label: Option<&str>) -> Result<String, String> {
         self.record_command(|r| r.dump_profile(label));
         let rprog = self.prog.lock().unwrap();
@@ -336,15 +828,28 @@ impl DDlogProfiling for HDDlog {
 impl DDlogDynamic for HDDlog {
     fn transaction_start(&self) -> Result<(), String> {
         self.record_command(|r| r.transaction_start());
-        self.prog.lock().unwrap().transaction_start()
+        // Let pending checkpoints go first (see `checkpoint`).
+        self.transaction_done
+            .wait_while(self.prog.lock().unwrap(), |_| {
+                self.checkpoints_pending.load(Ordering::SeqCst) > 0
+            })
+            .unwrap()
+            .transaction_start()?;
+        self.shadow_command(|shadow| shadow.candidate.transaction_start());
+        Ok(())
     }
 
     fn transaction_commit(&self) -> Result<(), String> {
         self.record_command(|r| r.transaction_commit());
+        if self.shadow.is_some() {
+            // Shadow evaluation compares the changes made by the transaction.
+            return self.do_transaction_commit_dump_changes(false).map(|_| ());
+        }
+        let _commit_guard = self.commit_lock.write().unwrap();
         self.update_handler.before_commit();
 
-        match self.prog.lock().unwrap().transaction_commit() {
-            Ok(()) => {
+        match self.commit_program() {
+            Ok(_) => {
                 self.update_handler.after_commit(true);
                 Ok(())
             }
@@ -362,6 +867,7 @@ impl DDlogDynamic for HDDlog {
         self.record_command(|r| r.transaction_commit_dump_changes_dynamic());
         Ok(self
             .do_transaction_commit_dump_changes(false)?
+            .0
             .into_iter()
             .map(|(relid, delta_typed)| {
                 let delta_dynamic: Vec<(Record, isize)> = delta_typed
@@ -375,12 +881,28 @@ impl DDlogDynamic for HDDlog {
 
     fn transaction_rollback(&self) -> Result<(), String> {
         self.record_command(|r| r.transaction_rollback());
-        self.prog.lock().unwrap().transaction_rollback()
+        let res = self.prog.lock().unwrap().transaction_rollback();
+        self.transaction_done.notify_all();
+        self.shadow_command(|shadow| shadow.abort());
+        res
     }
 
     fn clear_relation(&self, table: RelId) -> Result<(), String> {
         self.record_command(|r| r.clear_relation(table));
-        self.prog.lock().unwrap().clear_relation(table)
+        let res = self.prog.lock().unwrap().clear_relation(table);
+        self.shadow_command(|shadow| {
+            if res.is_err() {
+                return shadow.abort();
+            }
+            let table = shadow.translate_relid(&*self.inventory, table)?;
+            shadow.candidate.clear_relation(table)
+        });
+
+        // A failed update rolls back the transaction.
+        if res.is_err() {
+            self.transaction_done.notify_all();
+        }
+        res
     }
 
     fn apply_updates_dynamic(&self, upds: &mut dyn Iterator<Item = UpdCmd>) -> Result<(), String> {
@@ -391,7 +913,7 @@ impl DDlogDynamic for HDDlog {
         // the first invalid command.
         // XXX: We must iterate till the end of `upds`, as `ddlog_apply_updates` relies on this to
         // deallocate all commands.
-        let convert = |update: UpdCmd| {
+        let convert = |(idx, update): (usize, UpdCmd)| {
             if conversion_err {
                 None
             } else {
@@ -399,7 +921,10 @@ impl DDlogDynamic for HDDlog {
                     Ok(u) => Some(u),
                     Err(e) => {
                         conversion_err = true;
-                        msg = Some(format!("invalid command {:?}: {}", update, e));
+                        msg = Some(format!(
+                            "update #{}: invalid command {:?}: {}",
+                            idx, update, e
+                        ));
                         None
                     }
                 }
@@ -411,20 +936,24 @@ impl DDlogDynamic for HDDlog {
             self.record_command(|r| r.apply_updates_dynamic(&mut update_vec.iter().cloned()));
 
             self.do_apply_updates(
-                &mut update_vec.into_iter().flat_map(convert)
+                &mut update_vec.into_iter().enumerate().flat_map(convert)
                     as &mut dyn Iterator<Item = Update<DDValue>>,
                 false,
             )
         } else {
             self.do_apply_updates(
-                &mut upds.flat_map(convert) as &mut dyn Iterator<Item = Update<DDValue>>,
+                &mut upds.enumerate().flat_map(convert)
+                    as &mut dyn Iterator<Item = Update<DDValue>>,
                 false,
             )
         };
 
-        match msg {
-            Some(e) => Err(e),
-            None => res,
+        match (msg, res) {
+            // Roll back updates converted before the invalid command, as
+            // `apply_updates` does for updates it rejects.
+            (Some(e), Ok(())) => Err(self.rollback_after_error(e)),
+            (Some(e), Err(_)) => Err(e),
+            (None, res) => res,
         }
     }
 
@@ -451,7 +980,7 @@ impl DDlogDynamic for HDDlog {
     }
 
     fn stop(&self) -> Result<(), String> {
-        self.prog.lock().unwrap().stop()
+        self.stop_with_report().map(|_| ())
     }
 }
 
@@ -459,30 +988,50 @@ impl DDlogDynamic for HDDlog {
 // flag is set to `true` when invoked from `impl DDlog` and `false` when invoked from `impl
 // DDlogDynamic`, since the latter does its own recording.
 impl HDDlog {
+    /// Also returns the time spent committing the transaction (see
+    /// `commit_program`).
     fn do_transaction_commit_dump_changes(
         &self,
         record: bool,
-    ) -> Result<DeltaMap<DDValue>, String> {
+    ) -> Result<(DeltaMap<DDValue>, Duration), String> {
         if record {
             self.record_command(|r| r.transaction_commit_dump_changes());
         }
+        let _commit_guard = self.commit_lock.write().unwrap();
         *self.deltadb.lock().unwrap() = Some(DeltaMap::new());
 
         self.update_handler.before_commit();
-        match self.prog.lock().unwrap().transaction_commit() {
-            Ok(()) => {
+        match self.commit_program() {
+            Ok(duration) => {
                 self.update_handler.after_commit(true);
-                let mut delta = self.deltadb.lock().unwrap();
-                Ok(delta.take().unwrap())
+                let delta = self.deltadb.lock().unwrap().take().unwrap();
+                self.shadow_command(|shadow| {
+                    let candidate_delta = shadow.candidate.transaction_commit_dump_changes()?;
+                    shadow.compare(&*self.inventory, &delta, &candidate_delta);
+                    Ok(())
+                });
+                Ok((delta, duration))
             }
 
             Err(e) => {
                 self.update_handler.after_commit(false);
+                self.shadow_command(|shadow| shadow.abort());
                 Err(e)
             }
         }
     }
 
+    /// Commits the current transaction, and records and returns its latency.
+    fn commit_program(&self) -> Result<Duration, String> {
+        let start = Instant::now();
+        let res = self.prog.lock().unwrap().transaction_commit();
+        self.transaction_done.notify_all();
+        res?;
+        let duration = start.elapsed();
+        self.commit_latency.observe(duration);
+        Ok(duration)
+    }
+
     fn do_apply_updates(
         &self,
         upds: &mut dyn Iterator<Item = Update<DDValue>>,
@@ -505,20 +1054,49 @@ impl HDDlog {
             Ok(())
         };
 
-        if record && self.command_recorder.is_some() {
+        let res = if (record && self.command_recorder.is_some()) || self.shadow.is_some() {
             let update_vec: Vec<_> = upds.collect();
-            self.record_command(|r| r.apply_updates(&mut update_vec.iter().cloned()));
+            if record {
+                self.record_command(|r| r.apply_updates(&mut update_vec.iter().cloned()));
+            }
 
-            self.prog
+            let res = self
+                .prog
                 .lock()
                 .unwrap()
-                .apply_updates(&mut update_vec.into_iter(), inspect_update)
+                .apply_updates(&mut update_vec.iter().cloned(), inspect_update);
+            self.shadow_command(|shadow| {
+                if res.is_err() {
+                    return shadow.abort();
+                }
+                let updates = update_vec
+                    .into_iter()
+                    .map(|update| shadow.translate(&*self.inventory, update))
+                    .collect::<Result<Vec<_>, String>>();
+                match updates {
+                    Ok(updates) => shadow.candidate.apply_updates(&mut updates.into_iter()),
+                    Err(e) => {
+                        // The candidate cannot apply the same updates as the
+                        // primary; abandon its transaction rather than commit
+                        // a different one.
+                        shadow.abort()?;
+                        Err(e)
+                    }
+                }
+            });
+            res
         } else {
             self.prog
                 .lock()
                 .unwrap()
                 .apply_updates(upds, inspect_update)
+        };
+
+        // A failed update rolls back the transaction.
+        if res.is_err() {
+            self.transaction_done.notify_all();
         }
+        res
     }
 
     fn do_query_index(
@@ -530,10 +1108,7 @@ impl HDDlog {
         if record {
             self.record_command(|r| r.query_index(index, key.clone()));
         }
-        let arrangement_id = self
-            .inventory
-            .index_to_arrangement_id(index)
-            .ok_or_else(|| format!("unknown index {}", index))?;
+        let arrangement_id = self.index_to_arrangement_id(index)?;
 
         self.prog
             .lock()
@@ -545,10 +1120,7 @@ impl HDDlog {
         if record {
             self.record_command(|r| r.dump_index(index));
         }
-        let arrangement_id = self
-            .inventory
-            .index_to_arrangement_id(index)
-            .ok_or_else(|| format!("unknown index {}", index))?;
+        let arrangement_id = self.index_to_arrangement_id(index)?;
 
         self.prog.lock().unwrap().dump_arrangement(arrangement_id)
     }
@@ -557,6 +1129,7 @@ impl HDDlog {
 impl DDlog for HDDlog {
     fn transaction_commit_dump_changes(&self) -> Result<DeltaMap<DDValue>, String> {
         self.do_transaction_commit_dump_changes(true)
+            .map(|(delta, _)| delta)
     }
 
     fn apply_updates(&self, upds: &mut dyn Iterator<Item = Update<DDValue>>) -> Result<(), String> {
@@ -0,0 +1,159 @@
+//! Shadow evaluation: applying the same transactions to a candidate version of
+//! a program and comparing its output with that of the primary (see
+//! `HDDlog::enable_shadow`).
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    api::HDDlog,
+    ddval::DDValue,
+    program::{RelId, Update},
+    record::{IntoRecord, Record},
+    DDlogDynamic, DDlogInventory, DeltaMap,
+};
+
+/// Callback invoked for each divergence found by shadow evaluation.
+pub type DivergenceCallback = Box<dyn Fn(&Divergence) + Send + Sync>;
+
+/// Difference between the changes that the primary and the candidate program
+/// made to a relation in the same transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Name of the relation.
+    pub relation: String,
+    /// Changes made by the primary, but not by the candidate.
+    pub primary: Vec<(Record, isize)>,
+    /// Changes made by the candidate, but not by the primary.
+    pub candidate: Vec<(Record, isize)>,
+}
+
+/// Changes to a relation, keyed by the textual representation of each value.
+/// Values of two different programs can only be compared as `Record`s, which
+/// are not `Ord`.
+type Changes = BTreeMap<String, (Record, isize)>;
+
+pub(super) struct Shadow {
+    pub(super) candidate: HDDlog,
+    on_divergence: DivergenceCallback,
+}
+
+impl Shadow {
+    pub(super) fn new(candidate: HDDlog, on_divergence: DivergenceCallback) -> Self {
+        Self {
+            candidate,
+            on_divergence,
+        }
+    }
+
+    /// Returns the id of the candidate's relation with the same name as the
+    /// primary's relation `relid`.
+    pub(super) fn translate_relid(
+        &self,
+        primary: &dyn DDlogInventory,
+        relid: RelId,
+    ) -> Result<RelId, String> {
+        let name = primary.get_table_name(relid)?;
+        self.candidate.inventory.get_table_id(name)
+    }
+
+    /// Translates an update of the primary into an update of the candidate's
+    /// relation with the same name.  The value is passed unchanged, so the
+    /// two relations must have the same type.
+    pub(super) fn translate(
+        &self,
+        primary: &dyn DDlogInventory,
+        update: Update<DDValue>,
+    ) -> Result<Update<DDValue>, String> {
+        let relid = self.translate_relid(primary, update.relid())?;
+        Ok(match update {
+            Update::Insert { v, .. } => Update::Insert { relid, v },
+            Update::InsertOrUpdate { v, .. } => Update::InsertOrUpdate { relid, v },
+            Update::DeleteValue { v, .. } => Update::DeleteValue { relid, v },
+            Update::DeleteKey { k, .. } => Update::DeleteKey { relid, k },
+            Update::Modify { k, m, .. } => Update::Modify { relid, k, m },
+        })
+    }
+
+    /// Rolls back the candidate's transaction, if any, after the primary's
+    /// transaction failed or was rolled back.
+    pub(super) fn abort(&self) -> Result<(), String> {
+        if self
+            .candidate
+            .prog
+            .lock()
+            .unwrap()
+            .transaction_in_progress()
+        {
+            self.candidate.transaction_rollback()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Compares the changes made by the primary and the candidate in the same
+    /// transaction, matching relations by name, and reports each relation on
+    /// which they differ.
+    pub(super) fn compare(
+        &self,
+        primary: &dyn DDlogInventory,
+        primary_delta: &DeltaMap<DDValue>,
+        candidate_delta: &DeltaMap<DDValue>,
+    ) {
+        let primary_changes = Self::changes_by_name(primary, primary_delta);
+        let candidate_changes = Self::changes_by_name(&*self.candidate.inventory, candidate_delta);
+
+        let relations: BTreeSet<&String> = primary_changes
+            .keys()
+            .chain(candidate_changes.keys())
+            .collect();
+        let no_changes = Changes::new();
+        for relation in relations {
+            let primary = primary_changes.get(relation).unwrap_or(&no_changes);
+            let candidate = candidate_changes.get(relation).unwrap_or(&no_changes);
+            let divergence = Divergence {
+                relation: relation.clone(),
+                primary: Self::difference(primary, candidate),
+                candidate: Self::difference(candidate, primary),
+            };
+            if !divergence.primary.is_empty() || !divergence.candidate.is_empty() {
+                (self.on_divergence)(&divergence);
+            }
+        }
+    }
+
+    fn changes_by_name(
+        inventory: &dyn DDlogInventory,
+        delta: &DeltaMap<DDValue>,
+    ) -> BTreeMap<String, Changes> {
+        delta
+            .iter()
+            .filter(|(_, rel)| !rel.is_empty())
+            .map(|(relid, rel)| {
+                let name = inventory
+                    .get_table_name(*relid)
+                    .map_or_else(|_| relid.to_string(), |name| name.to_string());
+                let changes = rel
+                    .iter()
+                    .map(|(v, weight)| {
+                        let record = v.clone().into_record();
+                        (record.to_string(), (record, *weight))
+                    })
+                    .collect();
+                (name, changes)
+            })
+            .collect()
+    }
+
+    /// Changes in `changes` that do not appear in `other` with the same weight.
+    fn difference(changes: &Changes, other: &Changes) -> Vec<(Record, isize)> {
+        changes
+            .iter()
+            .filter(|(key, (_, weight))| {
+                other
+                    .get(*key)
+                    .map_or(true, |(_, other_weight)| other_weight != weight)
+            })
+            .map(|(_, change)| change.clone())
+            .collect()
+    }
+}
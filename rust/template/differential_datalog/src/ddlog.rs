@@ -21,7 +21,7 @@ use crate::record::UpdCmd;
 use crate::record::{Record, RelIdentifier};
 use crate::valmap::DeltaMap;
 
-use ddlog_profiler::{CpuProfile, SizeProfileRecord};
+use ddlog_profiler::{CpuProfile, RuleProfile, SizeProfileRecord};
 
 /// Convert relation and index names to and from numeric id's.
 pub trait DDlogInventory: DynClone {
@@ -390,6 +390,17 @@ pub trait DDlogProfiling {
     ///
     /// Fails if the program runs with self-profiler is disabled.
     fn cpu_profile(&self) -> Result<Option<CpuProfile>, String>;
+
+    /// Returns CPU profile aggregated by source code location (see
+    /// `ddlog_profiler::Profile::rule_profile`) or `None` if CPU profiling
+    /// was never enabled.
+    ///
+    /// Implementations fail if the program runs with self-profiler
+    /// disabled.  The default implementation, for programs that do not
+    /// support rule profiles, returns `Ok(None)`.
+    fn rule_profile(&self) -> Result<Option<Vec<RuleProfile>>, String> {
+        Ok(None)
+    }
 }
 
 /// API to dump DDlog input and output relations.
@@ -423,6 +434,9 @@ pub trait DDlogDynamic {
     fn transaction_rollback(&self) -> Result<(), String>;
 
     /// Apply a set of updates.
+    ///
+    /// If any update fails, the entire transaction is rolled back and
+    /// must be restarted with `transaction_start`.
     fn apply_updates_dynamic(&self, upds: &mut dyn Iterator<Item = UpdCmd>) -> Result<(), String>;
 
     fn clear_relation(&self, table: RelId) -> Result<(), String>;
@@ -445,6 +459,9 @@ pub trait DDlog: DDlogDynamic {
     fn transaction_commit_dump_changes(&self) -> Result<DeltaMap<DDValue>, String>;
 
     /// Apply a set of updates.
+    ///
+    /// If any update fails, the entire transaction is rolled back and
+    /// must be restarted with `transaction_start`.
     fn apply_updates(&self, upds: &mut dyn Iterator<Item = Update<DDValue>>) -> Result<(), String>;
 
     /// Query index.  Returns all values associated with the given key in the index.
@@ -0,0 +1,242 @@
+//! Latency histograms with Prometheus-style cumulative buckets.
+//!
+//! `Histogram` is the common building block for latency metrics, such as the
+//! commit latency tracked by `HDDlog::commit_latency`: observations are
+//! recorded with `observe()` from any thread, and `snapshot()` returns a
+//! consistent-enough copy of the counters that can be inspected, used to
+//! estimate quantiles, or rendered in the Prometheus text exposition format.
+
+use std::{
+    fmt::{self, Write},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Default bucket upper bounds, in milliseconds.  Same as the default buckets
+/// used by Prometheus client libraries.
+const DEFAULT_BUCKETS_MS: [u64; 11] = [5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+/// A latency histogram with a fixed set of buckets.
+#[derive(Debug)]
+pub struct Histogram {
+    /// Bucket upper bounds, sorted in ascending order.  The implicit last
+    /// bucket (`+Inf`) is not included.
+    bounds: Vec<Duration>,
+    /// Number of observations that fall in each bucket (non-cumulative).
+    /// Has one more element than `bounds` for the `+Inf` bucket.
+    counts: Vec<AtomicU64>,
+    /// Sum of all observations in nanoseconds.
+    sum_nanos: AtomicU64,
+}
+
+impl Histogram {
+    /// Creates a histogram with the given bucket upper bounds.  Bounds do not
+    /// need to be sorted; duplicates are ignored.
+    pub fn new(mut bounds: Vec<Duration>) -> Self {
+        bounds.sort_unstable();
+        bounds.dedup();
+        let counts = (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect();
+
+        Self {
+            bounds,
+            counts,
+            sum_nanos: AtomicU64::new(0),
+        }
+    }
+
+    /// Records one observation.
+    pub fn observe(&self, duration: Duration) {
+        // Index of the first bucket whose upper bound is `>= duration`, or
+        // the `+Inf` bucket.
+        let bucket = self.bounds.partition_point(|bound| *bound < duration);
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Returns a copy of the current state of the histogram.
+    ///
+    /// Observations recorded concurrently with `snapshot()` may or may not be
+    /// reflected in the result.
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let mut cumulative = 0;
+        let buckets = self
+            .bounds
+            .iter()
+            .zip(self.counts.iter())
+            .map(|(bound, count)| {
+                cumulative += count.load(Ordering::Relaxed);
+                (*bound, cumulative)
+            })
+            .collect();
+        let count = cumulative + self.counts[self.bounds.len()].load(Ordering::Relaxed);
+
+        HistogramSnapshot {
+            buckets,
+            count,
+            sum: Duration::from_nanos(self.sum_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self::new(
+            DEFAULT_BUCKETS_MS
+                .iter()
+                .map(|millis| Duration::from_millis(*millis))
+                .collect(),
+        )
+    }
+}
+
+/// A point-in-time copy of a `Histogram`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistogramSnapshot {
+    /// `(upper bound, cumulative count)` pairs for all finite buckets, in
+    /// ascending order of upper bound.  The count for the `+Inf` bucket is
+    /// `count`.
+    pub buckets: Vec<(Duration, u64)>,
+    /// Total number of observations.
+    pub count: u64,
+    /// Sum of all observations.
+    pub sum: Duration,
+}
+
+impl HistogramSnapshot {
+    /// Estimates the `q`-quantile (`0.0 <= q <= 1.0`) of the observations by
+    /// linear interpolation within the bucket that contains it, the same way
+    /// Prometheus' `histogram_quantile()` does.
+    ///
+    /// Returns `None` if the histogram is empty.  Quantiles that fall in the
+    /// `+Inf` bucket are reported as the largest finite bucket bound.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let rank = q.clamp(0.0, 1.0) * self.count as f64;
+        let mut lower_bound = Duration::from_secs(0);
+        let mut lower_count = 0;
+        for &(bound, count) in self.buckets.iter() {
+            if count as f64 >= rank && count > lower_count {
+                let fraction = (rank - lower_count as f64) / (count - lower_count) as f64;
+                let offset = ((bound - lower_bound).as_nanos() as f64 * fraction).round();
+                return Some(lower_bound + Duration::from_nanos(offset as u64));
+            }
+            lower_bound = bound;
+            lower_count = count;
+        }
+
+        Some(lower_bound)
+    }
+
+    /// Mean of all observations, or `None` if the histogram is empty.
+    pub fn mean(&self) -> Option<Duration> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(Duration::from_nanos(
+                (self.sum.as_nanos() / self.count as u128) as u64,
+            ))
+        }
+    }
+
+    /// Renders the snapshot as metric family `name` in the Prometheus text
+    /// exposition format.  Durations are reported in seconds.
+    pub fn to_prometheus(&self, name: &str) -> String {
+        let mut text = String::new();
+        self.write_prometheus(&mut text, name).unwrap();
+        text
+    }
+
+    /// Like `to_prometheus()`, but writes to `w`.
+    pub fn write_prometheus(&self, w: &mut dyn Write, name: &str) -> fmt::Result {
+        writeln!(w, "# TYPE {} histogram", name)?;
+        for (bound, count) in self.buckets.iter() {
+            writeln!(
+                w,
+                "{}_bucket{{le=\"{}\"}} {}",
+                name,
+                bound.as_secs_f64(),
+                count
+            )?;
+        }
+        writeln!(w, "{}_bucket{{le=\"+Inf\"}} {}", name, self.count)?;
+        writeln!(w, "{}_sum {}", name, self.sum.as_secs_f64())?;
+        writeln!(w, "{}_count {}", name, self.count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    /// Buckets at 10, 20, 50, 100ms with observations 1..=100ms.
+    fn histogram() -> Histogram {
+        let histogram = Histogram::new(vec![ms(100), ms(10), ms(50), ms(20), ms(10)]);
+        for millis in 1..=100 {
+            histogram.observe(ms(millis));
+        }
+        histogram
+    }
+
+    #[test]
+    fn bucket_counts() {
+        let snapshot = histogram().snapshot();
+        assert_eq!(
+            snapshot.buckets,
+            vec![(ms(10), 10), (ms(20), 20), (ms(50), 50), (ms(100), 100)]
+        );
+        assert_eq!(snapshot.count, 100);
+        assert_eq!(snapshot.sum, ms(5050));
+        assert_eq!(snapshot.mean(), Some(Duration::from_micros(50500)));
+    }
+
+    #[test]
+    fn overflow_bucket() {
+        let histogram = histogram();
+        histogram.observe(Duration::from_secs(1));
+
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.buckets.last(), Some(&(ms(100), 100)));
+        assert_eq!(snapshot.count, 101);
+        assert_eq!(snapshot.quantile(1.0), Some(ms(100)));
+    }
+
+    #[test]
+    fn quantiles() {
+        let snapshot = histogram().snapshot();
+        assert_eq!(snapshot.quantile(0.0), Some(ms(0)));
+        assert_eq!(snapshot.quantile(0.05), Some(ms(5)));
+        assert_eq!(snapshot.quantile(0.5), Some(ms(50)));
+        assert_eq!(snapshot.quantile(0.75), Some(ms(75)));
+        assert_eq!(snapshot.quantile(1.0), Some(ms(100)));
+
+        assert_eq!(Histogram::default().snapshot().quantile(0.5), None);
+    }
+
+    #[test]
+    fn prometheus_text() {
+        let histogram = Histogram::new(vec![ms(10), ms(250)]);
+        histogram.observe(ms(5));
+        histogram.observe(ms(100));
+        histogram.observe(ms(400));
+
+        assert_eq!(
+            histogram
+                .snapshot()
+                .to_prometheus("ddlog_commit_latency_seconds"),
+            "# TYPE ddlog_commit_latency_seconds histogram\n\
+             ddlog_commit_latency_seconds_bucket{le=\"0.01\"} 1\n\
+             ddlog_commit_latency_seconds_bucket{le=\"0.25\"} 2\n\
+             ddlog_commit_latency_seconds_bucket{le=\"+Inf\"} 3\n\
+             ddlog_commit_latency_seconds_sum 0.505\n\
+             ddlog_commit_latency_seconds_count 3\n"
+        );
+    }
+}
@@ -9,6 +9,7 @@ mod callback;
 mod dataflow;
 mod ddlog;
 pub mod flatbuf;
+pub mod histogram;
 mod render;
 pub mod replay;
 mod valmap;
@@ -31,4 +32,4 @@ pub use ddlog::{
 };
 pub use replay::CommandRecorder;
 pub use triomphe;
-pub use valmap::DeltaMap;
+pub use valmap::{DeltaMap, MerkleDiff, MerkleTree};
@@ -977,6 +977,8 @@ pub struct RunningProgram {
     relations: FnvHashMap<RelId, RelationInstance>,
     worker_guards: Option<WorkerGuards<Result<(), String>>>,
     transaction_in_progress: bool,
+    /// Number of successfully committed transactions.
+    commit_count: u64,
     need_to_flush: bool,
     timestamp: TS,
     /// CPU profiling enabled (can be expensive).
@@ -1217,6 +1219,7 @@ impl Program {
             relations: rels,
             worker_guards: Some(worker_guards),
             transaction_in_progress: false,
+            commit_count: 0,
             need_to_flush: false,
             timestamp: 1,
             profile_cpu: profiling_rig.profile_cpu,
@@ -2120,9 +2123,22 @@ impl RunningProgram {
         self.flush()?;
         self.delta_cleanup();
         self.transaction_in_progress = false;
+        self.commit_count += 1;
         Ok(())
     }
 
+    /// Returns `true` if there is a transaction in progress.
+    pub fn transaction_in_progress(&self) -> bool {
+        self.transaction_in_progress
+    }
+
+    /// Returns the number of transactions committed since the program was
+    /// started.  This number identifies the version of the program state
+    /// between transactions.
+    pub fn commit_count(&self) -> u64 {
+        self.commit_count
+    }
+
     /// Rollback the transaction, undoing all changes.
     pub fn transaction_rollback(&mut self) -> Response<()> {
         if !self.transaction_in_progress {
@@ -2196,6 +2212,13 @@ impl RunningProgram {
 
     /// Apply multiple insert and delete operations in one batch.
     /// Updates can only be applied to input relations (see `struct Relation`).
+    ///
+    /// Updates are applied all-or-nothing: if any update is rejected, either
+    /// by `inspect` or because it is invalid for its relation, the entire
+    /// transaction is rolled back, including changes made by earlier calls
+    /// in the same transaction, and the error identifies the index of the
+    /// failed update in `updates`.  The caller must start a new transaction
+    /// before applying more updates.
     pub fn apply_updates<I, F>(&mut self, updates: I, inspect: F) -> Response<()>
     where
         I: Iterator<Item = Update<DDValue>>,
@@ -2205,21 +2228,53 @@ impl RunningProgram {
             return Err("apply_updates: no transaction in progress".to_string());
         }
 
+        self.do_apply_updates(updates, inspect).or_else(|e| {
+            let e = format!("apply_updates: {}", e);
+            match self.transaction_rollback() {
+                Ok(()) => Err(format!("{} (transaction rolled back)", e)),
+                Err(rollback_err) => Err(format!(
+                    "{} (failed to roll back transaction: {})",
+                    e, rollback_err
+                )),
+            }
+        })
+    }
+
+    /// Applies updates to input relations and sends them to workers.  On
+    /// error, updates preceding the failed one are still sent, so that the
+    /// dataflow stays in sync with input relations, and the transaction can
+    /// be rolled back.
+    fn do_apply_updates<I, F>(&mut self, updates: I, inspect: F) -> Response<()>
+    where
+        I: Iterator<Item = Update<DDValue>>,
+        F: Fn(&Update<DDValue>) -> Response<()>,
+    {
         // Remove no-op updates to maintain set semantics
         let mut filtered_updates = Vec::new();
-        for update in updates {
-            inspect(&update)?;
-            self.apply_update(update, &mut filtered_updates)?;
+        let mut result = Ok(());
+        for (idx, update) in updates.enumerate() {
+            if let Err(e) =
+                inspect(&update).and_then(|_| self.apply_update(update, &mut filtered_updates))
+            {
+                result = Err(format!("update #{}: {}", idx, e));
+                break;
+            }
         }
 
-        if filtered_updates.is_empty() {
+        self.send_updates(filtered_updates).and(result)
+    }
+
+    /// Distributes updates that have already been applied to input relations
+    /// across workers.
+    fn send_updates(&mut self, updates: Vec<Update<DDValue>>) -> Response<()> {
+        if updates.is_empty() {
             return Ok(());
         }
 
         let mut worker_round_robbin = self.worker_round_robbin.clone();
 
-        let chunk_size = cmp::max(filtered_updates.len() / self.senders.len(), 5000);
-        filtered_updates
+        let chunk_size = cmp::max(updates.len() / self.senders.len(), 5000);
+        updates
             .chunks(chunk_size)
             .map(|chunk| Msg::Update {
                 updates: chunk.to_vec(),
@@ -2617,16 +2672,19 @@ impl RunningProgram {
 
             Update::Modify { relid, k, m } => match s.entry(k.clone()) {
                 hash_map::Entry::Occupied(mut oe) => {
-                    let new = oe.get_mut();
-                    let old: DDValue = (*new).clone();
-                    m.mutate(new)?;
+                    // Mutate a copy, so that a failed mutation does not leave
+                    // a partially modified value in the relation.
+                    let old: DDValue = oe.get().clone();
+                    let mut new = old.clone();
+                    m.mutate(&mut new)?;
                     Self::delta_dec(ds, &old);
                     updates.push(Update::DeleteValue { relid, v: old });
-                    Self::delta_inc(ds, new);
+                    Self::delta_inc(ds, &new);
                     updates.push(Update::Insert {
                         relid,
                         v: new.clone(),
                     });
+                    *oe.get_mut() = new;
 
                     Ok(())
                 }
@@ -2754,7 +2812,7 @@ impl RunningProgram {
         }
 
         // println!("updates: {:?}", updates);
-        self.apply_updates(updates.into_iter(), |_| Ok(()))
+        self.do_apply_updates(updates.into_iter(), |_| Ok(()))
             .and_then(|_| self.flush())
             .map(|_| {
                 /* validation: all deltas must be empty */
@@ -10,7 +10,7 @@ use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
     borrow::Cow,
     cell::{Cell, RefCell},
-    collections::{btree_map, BTreeMap, BTreeSet},
+    collections::{btree_map, BTreeMap, BTreeSet, HashMap},
     fmt::{self, Write},
     iter::FromIterator,
     rc::Rc,
@@ -176,6 +176,34 @@ impl Record {
         }
     }
 
+    /// Returns a copy of a named struct that only contains fields listed in
+    /// `field_names`, in the order they appear in the struct.  Returns
+    /// `Ok(None)` if the record is not a named struct and an error if one of
+    /// `field_names` is not a field of the struct.
+    pub fn project_struct_fields(&self, field_names: &[&str]) -> Result<Option<Self>, String> {
+        match self {
+            Self::NamedStruct(constructor, fields) => {
+                if let Some(missing) = field_names
+                    .iter()
+                    .find(|field| fields.iter().all(|(name, _)| name != *field))
+                {
+                    return Err(format!("struct {} has no field {}", constructor, missing));
+                }
+
+                Ok(Some(Self::NamedStruct(
+                    constructor.clone(),
+                    fields
+                        .iter()
+                        .filter(|(name, _)| field_names.iter().any(|field| name == field))
+                        .cloned()
+                        .collect(),
+                )))
+            }
+
+            _ => Ok(None),
+        }
+    }
+
     pub fn nth_struct_field(&self, idx: usize) -> Option<&Self> {
         match self {
             Self::PosStruct(_, fields) => fields.get(idx),
@@ -319,6 +347,42 @@ pub enum UpdCmd {
 }
 
 impl UpdCmd {
+    /// Returns the identifier of the relation the command applies to.
+    pub fn relation(&self) -> &RelIdentifier {
+        match self {
+            Self::Insert(relation_ident, _)
+            | Self::InsertOrUpdate(relation_ident, _)
+            | Self::Delete(relation_ident, _)
+            | Self::DeleteKey(relation_ident, _)
+            | Self::Modify(relation_ident, _, _) => relation_ident,
+        }
+    }
+
+    fn relation_mut(&mut self) -> &mut RelIdentifier {
+        match self {
+            Self::Insert(relation_ident, _)
+            | Self::InsertOrUpdate(relation_ident, _)
+            | Self::Delete(relation_ident, _)
+            | Self::DeleteKey(relation_ident, _)
+            | Self::Modify(relation_ident, _, _) => relation_ident,
+        }
+    }
+
+    /// Replaces the name of the relation the command applies to according
+    /// to `renames`, which maps old relation names to new ones.  This allows
+    /// commands recorded against an earlier version of a program to be
+    /// applied after a relation was renamed.
+    ///
+    /// Commands that identify the relation by id or by a name that is not
+    /// in `renames` are left unchanged.
+    pub fn rename_relation(&mut self, renames: &HashMap<Name, Name>) {
+        if let RelIdentifier::RelName(rname) = self.relation_mut() {
+            if let Some(new_name) = renames.get(rname) {
+                *rname = new_name.clone();
+            }
+        }
+    }
+
     pub fn to_update<I>(&self, inventory: &I) -> Result<Update<DDValue>, String>
     where
         I: DDlogInventory,
@@ -526,3 +526,41 @@ fn test_set() {
     .unwrap();
     assert_eq!(v, BTreeSet::from_iter(vec![1, 2]));
 }
+
+#[test]
+fn test_project_struct_fields() {
+    let record = Record::NamedStruct(
+        Name::from("Edge"),
+        vec![
+            (Name::from("from"), Record::Int(BigInt::from(1))),
+            (Name::from("to"), Record::Int(BigInt::from(2))),
+            (Name::from("label"), Record::String("a".to_string())),
+        ],
+    );
+
+    let projected = record
+        .project_struct_fields(&["label", "from"])
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        projected,
+        Record::NamedStruct(
+            Name::from("Edge"),
+            vec![
+                (Name::from("from"), Record::Int(BigInt::from(1))),
+                (Name::from("label"), Record::String("a".to_string())),
+            ],
+        )
+    );
+    assert_eq!(projected.get_struct_field("to"), None);
+
+    assert_eq!(
+        record.project_struct_fields(&["from", "weight"]),
+        Err("struct Edge has no field weight".to_string())
+    );
+
+    assert_eq!(
+        Record::Int(BigInt::from(1)).project_struct_fields(&["from"]),
+        Ok(None)
+    );
+}
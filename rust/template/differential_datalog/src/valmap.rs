@@ -5,10 +5,13 @@
 #![allow(non_snake_case, dead_code)]
 
 use std::collections::btree_map::{BTreeMap, Entry};
-use std::convert::{AsMut, AsRef};
+use std::convert::{AsMut, AsRef, TryFrom};
 use std::fmt::Display;
+use std::hash::{Hash, Hasher};
 use std::io;
 
+use fnv::FnvHasher;
+
 use crate::ddlog::DDlogInventory;
 use crate::program::RelId;
 
@@ -152,3 +155,190 @@ impl<V: Display + Ord + Clone> DeltaMap<V> {
         };
     }
 }
+
+impl<V: Hash> DeltaMap<V> {
+    /// Computes a digest of the contents of relation `relid`.
+    ///
+    /// The digest is the XOR of the hashes of all `(value, weight)` pairs in
+    /// the relation, so it does not depend on the order in which values were
+    /// inserted.  Two maps holding the same values with the same weights
+    /// produce the same digest, which makes it cheap to check whether two
+    /// copies of a relation agree.  An empty or missing relation has digest 0.
+    pub fn rel_digest(&self, relid: RelId) -> u64 {
+        self.map.get(&relid).map_or(0, |rel| {
+            rel.iter()
+                .fold(0, |digest, (val, weight)| digest ^ item_digest(val, weight))
+        })
+    }
+
+    /// Builds a Merkle tree with the given `fanout` and `depth` over the
+    /// contents of relation `relid`.
+    ///
+    /// Values are assigned to the `fanout^depth` leaves of the tree by their
+    /// hash, and each node holds the digest (in the sense of `rel_digest`) of
+    /// the values under it, so the root is equal to `rel_digest(relid)`.
+    /// Two copies of a relation built with the same parameters can be compared
+    /// with `MerkleTree::diff`, which only descends into subtrees that differ.
+    pub fn rel_merkle(
+        &self,
+        relid: RelId,
+        fanout: usize,
+        depth: usize,
+    ) -> Result<MerkleTree, String> {
+        let mut tree = MerkleTree::new(fanout, depth)?;
+        if let Some(rel) = self.map.get(&relid) {
+            for (val, weight) in rel.iter() {
+                let leaf = tree.leaf_index(val);
+                tree.levels[depth][leaf] ^= item_digest(val, weight);
+            }
+        }
+        tree.update_inner_nodes();
+        Ok(tree)
+    }
+}
+
+fn item_digest<V: Hash>(val: &V, weight: &isize) -> u64 {
+    let mut hasher = FnvHasher::default();
+    val.hash(&mut hasher);
+    weight.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The largest number of leaves `DeltaMap::rel_merkle` will allocate.
+const MAX_MERKLE_LEAVES: usize = 1 << 24;
+
+/// A Merkle tree over the contents of a relation, built by
+/// `DeltaMap::rel_merkle`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleTree {
+    fanout: usize,
+    /// `levels[0]` contains the root, `levels[depth]` the leaves.  Level `l`
+    /// has `fanout^l` nodes; the children of node `i` at level `l` are nodes
+    /// `i*fanout .. (i+1)*fanout` at level `l+1`.
+    levels: Vec<Vec<u64>>,
+}
+
+/// Result of comparing two Merkle trees with `MerkleTree::diff`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleDiff {
+    /// Indexes of the leaves whose digests differ, in ascending order.
+    pub leaves: Vec<usize>,
+    /// Number of node comparisons it took to find them.
+    pub comparisons: usize,
+}
+
+impl MerkleTree {
+    fn new(fanout: usize, depth: usize) -> Result<Self, String> {
+        if fanout < 2 {
+            return Err(format!(
+                "Merkle tree fanout must be at least 2, got {}",
+                fanout
+            ));
+        }
+        let leaves = match u32::try_from(depth)
+            .ok()
+            .and_then(|depth| fanout.checked_pow(depth))
+        {
+            Some(leaves) if leaves <= MAX_MERKLE_LEAVES => leaves,
+            _ => {
+                return Err(format!(
+                    "Merkle tree with fanout {} and depth {} exceeds {} leaves",
+                    fanout, depth, MAX_MERKLE_LEAVES
+                ))
+            }
+        };
+
+        // Derive the width of each level from the number of leaves, so that
+        // no width is computed beyond the one that was checked above.
+        let mut width = leaves;
+        let mut levels: Vec<Vec<u64>> = (0..=depth)
+            .map(|_| {
+                let level = vec![0; width];
+                width /= fanout;
+                level
+            })
+            .collect();
+        levels.reverse();
+        Ok(Self { fanout, levels })
+    }
+
+    fn update_inner_nodes(&mut self) {
+        for level in (0..self.depth()).rev() {
+            let (parents, children) = self.levels.split_at_mut(level + 1);
+            for (parent, chunk) in parents[level]
+                .iter_mut()
+                .zip(children[0].chunks(self.fanout))
+            {
+                *parent = chunk.iter().fold(0, |digest, child| digest ^ child);
+            }
+        }
+    }
+
+    /// Number of children of each inner node.
+    pub fn fanout(&self) -> usize {
+        self.fanout
+    }
+
+    /// Number of levels below the root; the tree has `fanout^depth` leaves.
+    pub fn depth(&self) -> usize {
+        self.levels.len() - 1
+    }
+
+    /// Digest of the entire relation.
+    pub fn root(&self) -> u64 {
+        self.levels[0][0]
+    }
+
+    /// Digest of node `index` at `level`, or `None` if there is no such node.
+    pub fn node(&self, level: usize, index: usize) -> Option<u64> {
+        self.levels.get(level)?.get(index).copied()
+    }
+
+    /// Digests of the leaves, i.e., of the buckets that values are hashed
+    /// into.
+    pub fn leaves(&self) -> &[u64] {
+        &self.levels[self.depth()]
+    }
+
+    /// Index of the leaf that `val` is assigned to.  Peers use it to select
+    /// the values of a relation that belong to a leaf reported by `diff`.
+    pub fn leaf_index<V: Hash>(&self, val: &V) -> usize {
+        let mut hasher = FnvHasher::default();
+        val.hash(&mut hasher);
+        (hasher.finish() % self.leaves().len() as u64) as usize
+    }
+
+    /// Finds the leaves whose digests differ between `self` and `other`,
+    /// descending from the root only into subtrees that differ.  Both trees
+    /// must have been built with the same fanout and depth.
+    pub fn diff(&self, other: &Self) -> Result<MerkleDiff, String> {
+        if self.fanout != other.fanout || self.depth() != other.depth() {
+            return Err(format!(
+                "cannot compare Merkle trees with different shapes: fanout {}, depth {} vs fanout {}, depth {}",
+                self.fanout,
+                self.depth(),
+                other.fanout,
+                other.depth()
+            ));
+        }
+
+        let mut diff = MerkleDiff {
+            leaves: Vec::new(),
+            comparisons: 0,
+        };
+        let mut stack = vec![(0, 0)];
+        while let Some((level, index)) = stack.pop() {
+            diff.comparisons += 1;
+            if self.levels[level][index] == other.levels[level][index] {
+                continue;
+            }
+            if level == self.depth() {
+                diff.leaves.push(index);
+            } else {
+                let children = index * self.fanout..(index + 1) * self.fanout;
+                stack.extend(children.rev().map(|child| (level + 1, child)));
+            }
+        }
+        Ok(diff)
+    }
+}
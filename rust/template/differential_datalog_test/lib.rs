@@ -9,12 +9,13 @@
 use std::borrow::Cow;
 use std::collections::btree_map::{BTreeMap, Entry};
 use std::collections::btree_set::BTreeSet;
+use std::convert::TryFrom;
 use std::sync::{Arc, Mutex};
 
 use ddlog_profiler::{
     ArrangementDebugInfo, DDlogSourceCode, OperatorDebugInfo, RuleDebugInfo, SourcePosition,
 };
-use differential_datalog::{ddval::*, program::config::Config, program::*};
+use differential_datalog::{ddval::*, program::config::Config, program::*, DeltaMap};
 use fnv::FnvHashMap;
 use num::One;
 use timely::communication::Allocator;
@@ -27,6 +28,9 @@ use differential_dataflow::Collection;
 pub mod test_value;
 use test_value::*;
 
+#[cfg(test)]
+mod test_hddlog;
+
 const TEST_SIZE: u64 = 1000;
 
 /*fn set_update(s: &Arc<Mutex<Delta>>, ds: &Arc<Mutex<DeltaSet<Value>>>, x : &Value, insert: bool)
@@ -134,12 +138,19 @@ fn test_insert_non_existent_relation() {
         )
         .unwrap();
     running.transaction_start().unwrap();
-    let result = running.insert(1, U64(42).into_ddvalue());
-    assert_eq!(
-        &result.unwrap_err(),
-        "apply_update: unknown input relation 1"
+    let err = running.insert(1, U64(42).into_ddvalue()).unwrap_err();
+    assert!(
+        err.contains("apply_update: unknown input relation 1"),
+        "unexpected error: {}",
+        err
+    );
+    // The failed update rolled back the transaction.
+    let err = running.transaction_commit().unwrap_err();
+    assert!(
+        err.contains("no transaction in progress"),
+        "unexpected error: {}",
+        err
     );
-    running.transaction_commit().unwrap();
 }
 
 #[test]
@@ -2025,3 +2036,76 @@ fn incorrect_from_ref_type() {
 
     let _panic = Empty::from_ddvalue_ref(&val);
 }
+
+/// Relation digests don't depend on insertion order, but change whenever the
+/// contents of the relation change.
+#[test]
+fn test_relation_digest() {
+    let mut db1: DeltaMap<DDValue> = DeltaMap::new();
+    let mut db2: DeltaMap<DDValue> = DeltaMap::new();
+
+    for x in 0..TEST_SIZE {
+        db1.update(1, &U64(x).into_ddvalue(), 1);
+    }
+    for x in (0..TEST_SIZE).rev() {
+        db2.update(1, &U64(x).into_ddvalue(), 1);
+    }
+    assert_eq!(db1.rel_digest(1), db2.rel_digest(1));
+
+    // Relations that were never populated have the same digest as an empty relation.
+    assert_eq!(db1.rel_digest(2), 0);
+
+    db2.update(1, &U64(0).into_ddvalue(), -1);
+    assert_ne!(db1.rel_digest(1), db2.rel_digest(1));
+
+    db2.update(1, &U64(0).into_ddvalue(), 1);
+    assert_eq!(db1.rel_digest(1), db2.rel_digest(1));
+
+    // Weights contribute to the digest.
+    db2.update(1, &U64(0).into_ddvalue(), 1);
+    assert_ne!(db1.rel_digest(1), db2.rel_digest(1));
+}
+
+/// Merkle trees over two relations that differ in a single value locate the
+/// leaf containing that value without comparing every leaf.
+#[test]
+fn test_relation_merkle() {
+    let mut db1: DeltaMap<DDValue> = DeltaMap::new();
+    let mut db2: DeltaMap<DDValue> = DeltaMap::new();
+
+    for x in 0..TEST_SIZE {
+        db1.update(1, &U64(x).into_ddvalue(), 1);
+        db2.update(1, &U64(x).into_ddvalue(), 1);
+    }
+    let extra = U64(TEST_SIZE).into_ddvalue();
+    db2.update(1, &extra, 1);
+
+    let tree1 = db1.rel_merkle(1, 4, 4).unwrap();
+    let tree2 = db2.rel_merkle(1, 4, 4).unwrap();
+    assert_eq!(tree1.leaves().len(), 256);
+    assert_eq!(tree1.root(), db1.rel_digest(1));
+    assert_eq!(tree1.diff(&tree1).unwrap().comparisons, 1);
+
+    // One path from the root to the leaf, comparing all siblings on the way.
+    let diff = tree1.diff(&tree2).unwrap();
+    assert_eq!(diff.leaves, vec![tree2.leaf_index(&extra)]);
+    assert_eq!(diff.comparisons, 1 + 4 * 4);
+
+    assert!(tree1.diff(&db1.rel_merkle(1, 4, 3).unwrap()).is_err());
+
+    // Level `l` has `fanout^l` nodes.
+    let tree3 = db1.rel_merkle(1, 3, 2).unwrap();
+    assert_eq!((tree3.fanout(), tree3.depth()), (3, 2));
+    assert_eq!(tree3.leaves().len(), 9);
+    assert!(tree3.node(1, 2).is_some());
+    assert_eq!(tree3.node(1, 3), None);
+    assert_eq!(tree3.root(), db1.rel_digest(1));
+
+    assert!(db1.rel_merkle(1, 1, 4).is_err());
+
+    // Depths that do not fit in `u32` must not wrap around.
+    assert!(db1.rel_merkle(1, 2, usize::MAX).is_err());
+    if let Ok(depth) = usize::try_from(1u64 << 32) {
+        assert!(db1.rel_merkle(1, 2, depth).is_err());
+    }
+}
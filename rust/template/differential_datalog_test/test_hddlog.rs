@@ -0,0 +1,805 @@
+//! Tests for the `HDDlog` API.
+//!
+//! The tests run a small hand-written program with two input relations
+//! `T1` and `T2`, and an output relation `T3 = T1 * 2`, all of type `U64`,
+//! plus an input relation `T4` of struct type `S`.  All relations are stored,
+//! so they can be inspected with `dump_table`.
+
+use std::any::TypeId;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use std::{env, fs, process, thread};
+
+use cmd_parser::{parse_command, Command};
+use ddlog_profiler::{OperatorDebugInfo, RuleDebugInfo, SourcePosition};
+use differential_datalog::{
+    api::{Divergence, HDDlog},
+    ddval::*,
+    flatbuf::UnimplementedFlatbufConverter,
+    program::config::{Config, ProfilingConfig},
+    program::*,
+    record::{FromRecord, IntoRecord, Name, Record, RelIdentifier, UpdCmd},
+    D3logLocalizer, D3logLocationId, DDlog, DDlogDump, DDlogDynamic, DDlogInventory,
+    DDlogProfiling, DeltaMap,
+};
+use fnv::FnvHashMap;
+use once_cell::sync::Lazy;
+
+use crate::test_value::{self, Uint, I64, Q, S, U64};
+use crate::SOURCE_CODE;
+
+pub const T1: RelId = 1;
+pub const T2: RelId = 2;
+pub const T3: RelId = 3;
+pub const T4: RelId = 4;
+
+static RELATION_NAMES: Lazy<FnvHashMap<RelId, &'static str>> = Lazy::new(|| {
+    vec![(T1, "T1"), (T2, "T2"), (T3, "T3"), (T4, "T4")]
+        .into_iter()
+        .collect()
+});
+
+static INPUT_RELATION_NAMES: Lazy<FnvHashMap<RelId, &'static str>> = Lazy::new(|| {
+    vec![(T1, "T1"), (T2, "T2"), (T4, "T4")]
+        .into_iter()
+        .collect()
+});
+
+fn input_relation(name: &'static str, id: RelId, cb: &Arc<dyn RelationCallback>) -> Relation {
+    Relation {
+        name: Cow::from(name),
+        source_pos: SourcePosition::Unknown,
+        input: true,
+        distinct: true,
+        caching_mode: CachingMode::Set,
+        key_func: None,
+        id,
+        rules: Vec::new(),
+        arrangements: Vec::new(),
+        change_cb: Some(cb.clone()),
+    }
+}
+
+/// Source position attributed to the rule that computes `T3`.
+fn t3_rule_pos() -> SourcePosition {
+    SourcePosition::new_location("test.dl", 3, 1)
+}
+
+fn double(v: DDValue) -> DDValue {
+    let &U64(uv) = U64::from_ddvalue_ref(&v);
+    U64(uv * 2).into_ddvalue()
+}
+
+fn triple(v: DDValue) -> DDValue {
+    let &U64(uv) = U64::from_ddvalue_ref(&v);
+    U64(uv * 3).into_ddvalue()
+}
+
+fn prog(update_cb: Arc<dyn RelationCallback>) -> Program {
+    prog_with_t3(update_cb, double)
+}
+
+/// A different version of the test program, where `T3 = T1 * 3`.
+fn candidate_prog(update_cb: Arc<dyn RelationCallback>) -> Program {
+    prog_with_t3(update_cb, triple)
+}
+
+/// Builds the test program, computing `T3` by applying `t3_func` to `T1`.
+fn prog_with_t3(update_cb: Arc<dyn RelationCallback>, t3_func: MapFunc) -> Program {
+    let t3 = Relation {
+        name: Cow::from("T3"),
+        source_pos: SourcePosition::Unknown,
+        input: false,
+        distinct: true,
+        caching_mode: CachingMode::Set,
+        key_func: None,
+        id: T3,
+        rules: vec![Rule::CollectionRule {
+            debug_info: RuleDebugInfo::default(),
+            rel: T1,
+            xform: Some(XFormCollection::Map {
+                debug_info: OperatorDebugInfo::map(t3_rule_pos()),
+                mfun: t3_func,
+                next: Box::new(None),
+            }),
+        }],
+        arrangements: Vec::new(),
+        change_cb: Some(update_cb.clone()),
+    };
+
+    Program {
+        nodes: vec![
+            ProgNode::Rel {
+                rel: input_relation("T1", T1, &update_cb),
+            },
+            ProgNode::Rel {
+                rel: input_relation("T2", T2, &update_cb),
+            },
+            ProgNode::Rel { rel: t3 },
+            ProgNode::Rel {
+                rel: input_relation("T4", T4, &update_cb),
+            },
+        ],
+        delayed_rels: vec![],
+        init_data: vec![],
+    }
+}
+
+#[derive(Clone)]
+struct TestInventory;
+
+impl TestInventory {
+    fn relation_id(relation: &RelIdentifier) -> Result<RelId, String> {
+        match relation {
+            RelIdentifier::RelName(name) => TestInventory.get_table_id(name),
+            RelIdentifier::RelId(id) if RELATION_NAMES.contains_key(id) => Ok(*id),
+            RelIdentifier::RelId(id) => Err(format!("unknown relation {}", id)),
+        }
+    }
+}
+
+impl DDlogInventory for TestInventory {
+    fn get_table_id(&self, table_name: &str) -> Result<RelId, String> {
+        RELATION_NAMES
+            .iter()
+            .find(|(_, name)| **name == table_name)
+            .map(|(&id, _)| id)
+            .ok_or_else(|| format!("unknown relation {}", table_name))
+    }
+
+    fn get_table_name(&self, table_id: RelId) -> Result<&'static str, String> {
+        RELATION_NAMES
+            .get(&table_id)
+            .cloned()
+            .ok_or_else(|| format!("unknown relation {}", table_id))
+    }
+
+    fn get_table_original_name(&self, table_name: &str) -> Result<&'static str, String> {
+        self.get_table_id(table_name)
+            .and_then(|id| self.get_table_name(id))
+    }
+
+    fn get_table_original_cname(&self, _table_name: &str) -> Result<&'static CStr, String> {
+        Err("not supported".to_string())
+    }
+
+    fn get_table_cname(&self, _table_id: RelId) -> Result<&'static CStr, String> {
+        Err("not supported".to_string())
+    }
+
+    fn get_index_id(&self, index_name: &str) -> Result<IdxId, String> {
+        Err(format!("unknown index {}", index_name))
+    }
+
+    fn get_index_name(&self, index_id: IdxId) -> Result<&'static str, String> {
+        Err(format!("unknown index {}", index_id))
+    }
+
+    fn get_index_cname(&self, index_id: IdxId) -> Result<&'static CStr, String> {
+        Err(format!("unknown index {}", index_id))
+    }
+
+    fn input_relation_ids(&self) -> &'static FnvHashMap<RelId, &'static str> {
+        &*INPUT_RELATION_NAMES
+    }
+
+    fn index_from_record(&self, index: IdxId, _key: &Record) -> Result<DDValue, String> {
+        Err(format!("unknown index {}", index))
+    }
+
+    fn relation_type_id(&self, relation: RelId) -> Option<TypeId> {
+        match relation {
+            T4 => Some(TypeId::of::<S>()),
+            _ if RELATION_NAMES.contains_key(&relation) => Some(TypeId::of::<U64>()),
+            _ => None,
+        }
+    }
+
+    fn relation_value_from_record(
+        &self,
+        relation: &RelIdentifier,
+        value: &Record,
+    ) -> Result<(RelId, DDValue), String> {
+        match Self::relation_id(relation)? {
+            T4 => S::from_record(value).map(|v| (T4, v.into_ddvalue())),
+            relid => U64::from_record(value).map(|v| (relid, v.into_ddvalue())),
+        }
+    }
+
+    fn relation_key_from_record(
+        &self,
+        relation: &RelIdentifier,
+        _key: &Record,
+    ) -> Result<(RelId, DDValue), String> {
+        Err(format!(
+            "relation {:?} does not have a primary key",
+            relation
+        ))
+    }
+
+    fn index_to_arrangement_id(&self, _index: IdxId) -> Option<ArrId> {
+        None
+    }
+}
+
+#[derive(Clone)]
+struct TestLocalizer;
+
+impl D3logLocalizer for TestLocalizer {
+    fn localize_value(
+        &self,
+        _relation: RelId,
+        value: DDValue,
+    ) -> Result<(Option<D3logLocationId>, RelId, DDValue), DDValue> {
+        Err(value)
+    }
+}
+
+/// Starts the test program with `workers` timely workers.
+pub fn run(workers: usize) -> (HDDlog, DeltaMap<DDValue>) {
+    run_with_config(Config::new().with_timely_workers(workers))
+}
+
+/// Starts the test program with `config`.
+pub fn run_with_config(config: Config) -> (HDDlog, DeltaMap<DDValue>) {
+    start(config, prog)
+}
+
+/// Starts the program built by `init_ddlog` with `config`.
+fn start(
+    config: Config,
+    init_ddlog: fn(Arc<dyn RelationCallback>) -> Program,
+) -> (HDDlog, DeltaMap<DDValue>) {
+    HDDlog::new(
+        config,
+        &SOURCE_CODE,
+        true,
+        None,
+        init_ddlog,
+        Box::new(TestInventory),
+        None,
+        Box::new(TestLocalizer),
+        Box::new(UnimplementedFlatbufConverter),
+    )
+    .unwrap()
+}
+
+pub fn insert(relid: RelId, x: u64) -> Update<DDValue> {
+    Update::Insert {
+        relid,
+        v: U64(x).into_ddvalue(),
+    }
+}
+
+/// Applies `updates` in a single transaction.
+pub fn commit(hddlog: &HDDlog, updates: Vec<Update<DDValue>>) {
+    hddlog.transaction_start().unwrap();
+    hddlog.apply_updates(&mut updates.into_iter()).unwrap();
+    hddlog.transaction_commit().unwrap();
+}
+
+/// Returns the contents of a stored relation as a sorted list of values.
+pub fn contents(dump: impl FnOnce(&dyn Fn(&Record, isize) -> bool)) -> Vec<u64> {
+    let values = RefCell::new(Vec::new());
+    dump(&|record, weight| {
+        assert_eq!(weight, 1);
+        values
+            .borrow_mut()
+            .push(U64::from_record(record).unwrap().0);
+        true
+    });
+
+    let mut values = values.into_inner();
+    values.sort_unstable();
+    values
+}
+
+pub fn table_contents(hddlog: &HDDlog, table: RelId) -> Vec<u64> {
+    contents(|cb| hddlog.dump_table(table, Some(cb)).unwrap())
+}
+
+/// Reads under `read_tx` observe the same state even while another thread commits.
+#[test]
+fn read_tx_is_consistent() {
+    let hddlog = Arc::new(run(2).0);
+
+    let writer = {
+        let hddlog = hddlog.clone();
+        thread::spawn(move || {
+            for x in 0..100 {
+                commit(&hddlog, vec![insert(T1, x), insert(T2, x)]);
+            }
+        })
+    };
+
+    for _ in 0..100 {
+        let (t1, t2, t3) = hddlog.read_tx(|tx| {
+            let t1 = contents(|cb| tx.dump_table(T1, Some(cb)).unwrap());
+            let t2 = contents(|cb| tx.dump_table(T2, Some(cb)).unwrap());
+            let t3 = contents(|cb| tx.dump_table(T3, Some(cb)).unwrap());
+            (t1, t2, t3)
+        });
+        assert_eq!(t1, t2);
+        assert_eq!(t1.iter().map(|x| x * 2).collect::<Vec<_>>(), t3);
+    }
+
+    writer.join().unwrap();
+    assert_eq!(table_contents(&hddlog, T2), (0..100).collect::<Vec<_>>());
+    hddlog.stop().unwrap();
+}
+
+/// A failed update rolls back the whole transaction, including updates
+/// applied before it.
+#[test]
+fn apply_updates_is_all_or_nothing() {
+    let (hddlog, _) = run(1);
+    commit(&hddlog, vec![insert(T1, 1)]);
+
+    hddlog.transaction_start().unwrap();
+    hddlog
+        .apply_updates(&mut vec![insert(T1, 2)].into_iter())
+        .unwrap();
+    let bad_update = Update::Insert {
+        relid: T2,
+        v: I64(3).into_ddvalue(),
+    };
+    let err = hddlog
+        .apply_updates(
+            &mut vec![insert(T1, 3), insert(T2, 3), bad_update, insert(T2, 4)].into_iter(),
+        )
+        .unwrap_err();
+    assert!(err.contains("update #2"), "unexpected error: {}", err);
+    assert!(hddlog.transaction_commit().is_err());
+
+    assert_eq!(table_contents(&hddlog, T1), vec![1]);
+    assert_eq!(table_contents(&hddlog, T2), Vec::<u64>::new());
+    assert_eq!(table_contents(&hddlog, T3), vec![2]);
+
+    // Commands that fail to convert roll back the transaction as well.
+    hddlog.transaction_start().unwrap();
+    let err = hddlog
+        .apply_updates_dynamic(
+            &mut vec![
+                UpdCmd::Insert(
+                    RelIdentifier::RelName(Name::from("T1")),
+                    U64(4).into_record(),
+                ),
+                UpdCmd::Insert(
+                    RelIdentifier::RelName(Name::from("T9")),
+                    U64(4).into_record(),
+                ),
+            ]
+            .into_iter(),
+        )
+        .unwrap_err();
+    assert!(err.starts_with("update #1:"), "unexpected error: {}", err);
+    assert!(hddlog.transaction_commit().is_err());
+    assert_eq!(table_contents(&hddlog, T1), vec![1]);
+
+    // The program accepts new transactions after the rollback.
+    commit(&hddlog, vec![insert(T1, 3)]);
+    assert_eq!(table_contents(&hddlog, T3), vec![2, 6]);
+    hddlog.stop().unwrap();
+}
+
+/// Commands that use the old name of a renamed relation are applied to the
+/// relation with the new name.
+#[test]
+fn relation_renames() {
+    let (mut hddlog, _) = run(1);
+    hddlog.set_relation_renames(
+        vec![(Name::from("OldT1"), Name::from("T1"))]
+            .into_iter()
+            .collect(),
+    );
+
+    hddlog.transaction_start().unwrap();
+    hddlog
+        .apply_updates_dynamic(
+            &mut vec![
+                UpdCmd::Insert(
+                    RelIdentifier::RelName(Name::from("OldT1")),
+                    U64(5).into_record(),
+                ),
+                UpdCmd::Insert(
+                    RelIdentifier::RelName(Name::from("T2")),
+                    U64(6).into_record(),
+                ),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+    hddlog.transaction_commit().unwrap();
+
+    assert_eq!(table_contents(&hddlog, T1), vec![5]);
+    assert_eq!(table_contents(&hddlog, T2), vec![6]);
+    assert_eq!(table_contents(&hddlog, T3), vec![10]);
+    hddlog.stop().unwrap();
+}
+
+/// `EvalStats` reflect the delta produced by the transaction, and commits are
+/// recorded in the commit latency histogram.
+#[test]
+fn commit_stats() {
+    let (hddlog, _) = run(1);
+
+    hddlog.transaction_start().unwrap();
+    hddlog
+        .apply_updates(&mut vec![insert(T1, 1), insert(T1, 2)].into_iter())
+        .unwrap();
+    let (delta, stats) = hddlog.transaction_commit_dump_changes_with_stats().unwrap();
+
+    // Two changes to `T1` and two derived changes to `T3`.
+    assert_eq!(stats.changes, 4);
+    assert_eq!(
+        stats.changes,
+        delta.values().map(|rel| rel.len()).sum::<usize>()
+    );
+    assert_eq!(stats.relations_touched, vec![T1, T3].into_iter().collect());
+    // `commit_duration` is the latency recorded in the histogram.
+    assert_eq!(hddlog.commit_latency().sum, stats.commit_duration);
+
+    // Both commits are counted; the initial transaction is not.
+    commit(&hddlog, vec![insert(T1, 3)]);
+    let latency = hddlog.commit_latency();
+    assert_eq!(latency.count, 2);
+    hddlog.stop().unwrap();
+}
+
+/// With CPU profiling enabled, `rule_profile` reports the time spent in the
+/// rule that computes `T3`.
+#[test]
+fn rule_profile() {
+    let (hddlog, _) = run_with_config(Config::new().with_timely_workers(1).with_profiling_config(
+        ProfilingConfig::SelfProfiling {
+            profile_directory: None,
+        },
+    ));
+    hddlog.enable_cpu_profiling(true).unwrap();
+
+    // Profiling events are processed asynchronously, so keep committing
+    // transactions until they show up in the profile.
+    let mut x = 0;
+    let rule = loop {
+        commit(&hddlog, vec![insert(T1, x)]);
+        let rule = hddlog
+            .rule_profile()
+            .unwrap()
+            .unwrap_or_default()
+            .into_iter()
+            .find(|rule| rule.location == t3_rule_pos() && rule.cpu_time > Duration::from_secs(0));
+        if let Some(rule) = rule {
+            break rule;
+        }
+        x += 1;
+        assert!(x < 1000, "no CPU time reported for the T3 rule");
+        thread::sleep(Duration::from_millis(10));
+    };
+    assert!(rule.invocations > 0);
+    hddlog.stop().unwrap();
+}
+
+/// Shadow evaluation reports the changes on which a candidate program differs
+/// from the primary, while the output of the primary stays unaffected.
+#[test]
+fn shadow() {
+    let (mut hddlog, _) = run(1);
+    let (candidate, _) = start(Config::new().with_timely_workers(1), candidate_prog);
+    let divergences = Arc::new(Mutex::new(Vec::new()));
+    {
+        let divergences = divergences.clone();
+        hddlog.enable_shadow(
+            candidate,
+            Box::new(move |divergence| divergences.lock().unwrap().push(divergence.clone())),
+        );
+    }
+
+    commit(&hddlog, vec![insert(T1, 1), insert(T2, 1)]);
+    hddlog.transaction_start().unwrap();
+    hddlog
+        .apply_updates(&mut vec![insert(T1, 2)].into_iter())
+        .unwrap();
+    let delta = hddlog.transaction_commit_dump_changes().unwrap();
+    assert_eq!(
+        delta.try_get_rel(T3).unwrap().keys().collect::<Vec<_>>(),
+        vec![&U64(4).into_ddvalue()]
+    );
+
+    // A failed update is rolled back in both programs.
+    hddlog.transaction_start().unwrap();
+    let bad_update = Update::Insert {
+        relid: T1,
+        v: I64(3).into_ddvalue(),
+    };
+    assert!(hddlog
+        .apply_updates(&mut vec![insert(T1, 3), bad_update].into_iter())
+        .is_err());
+
+    // Only `T3` diverges; the inputs are the same in both programs.
+    let divergence = |primary: u64, candidate: u64| Divergence {
+        relation: "T3".to_string(),
+        primary: vec![(U64(primary).into_record(), 1)],
+        candidate: vec![(U64(candidate).into_record(), 1)],
+    };
+    assert_eq!(
+        *divergences.lock().unwrap(),
+        vec![divergence(2, 3), divergence(4, 6)]
+    );
+
+    assert_eq!(table_contents(&hddlog, T3), vec![2, 4]);
+    let candidate = hddlog.disable_shadow().unwrap();
+    assert_eq!(table_contents(&candidate, T1), vec![1, 2]);
+    assert_eq!(table_contents(&candidate, T3), vec![3, 6]);
+    candidate.stop().unwrap();
+    hddlog.stop().unwrap();
+}
+
+/// The shutdown report summarizes the session.
+#[test]
+fn shutdown_report() {
+    let (hddlog, _) = run(1);
+    commit(&hddlog, vec![insert(T1, 1), insert(T1, 2), insert(T2, 3)]);
+    commit(&hddlog, vec![insert(T2, 4)]);
+
+    let report = hddlog.stop_with_report().unwrap();
+    assert_eq!(report.transactions_committed, 2);
+    assert!(report.uptime > Duration::from_secs(0));
+    assert_eq!(
+        report.relation_sizes,
+        vec![
+            ("T1".to_string(), 2),
+            ("T2".to_string(), 2),
+            ("T3".to_string(), 2)
+        ]
+        .into_iter()
+        .collect()
+    );
+}
+
+/// Parses a checkpoint written by `HDDlog::checkpoint`, checking that it
+/// consists of the version comment followed by a single transaction, and
+/// returns the version and the updates of the transaction.
+fn read_checkpoint(path: &Path) -> (u64, Vec<UpdCmd>) {
+    let contents = fs::read_to_string(path).unwrap();
+    let (header, body) = contents.split_at(contents.find('\n').unwrap() + 1);
+    let version = header
+        .trim_end()
+        .strip_prefix("# checkpoint version ")
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    let mut commands = Vec::new();
+    let mut input = body.as_bytes();
+    while !input.iter().all(u8::is_ascii_whitespace) {
+        let (rest, command) = parse_command(input).unwrap();
+        commands.push(command);
+        input = rest;
+    }
+    assert_eq!(commands.remove(0), Command::Start);
+    assert_eq!(commands.pop(), Some(Command::Commit(false)));
+
+    let count = commands.len();
+    let updates = commands
+        .into_iter()
+        .enumerate()
+        .map(|(i, command)| match command {
+            // Only the last update is followed by ";".
+            Command::Update(update, last) => {
+                assert_eq!(last, i + 1 == count);
+                update
+            }
+            command => panic!("unexpected command {:?} in checkpoint", command),
+        })
+        .collect();
+    (version, updates)
+}
+
+/// Returns the values inserted by `updates` as a sorted list of
+/// `(relation, value)` pairs.
+fn inserted_values(updates: &[UpdCmd]) -> Vec<(String, u64)> {
+    let mut values: Vec<(String, u64)> = updates
+        .iter()
+        .map(|update| match update {
+            UpdCmd::Insert(RelIdentifier::RelName(name), record) => {
+                (name.to_string(), U64::from_record(record).unwrap().0)
+            }
+            update => panic!("unexpected update {:?} in checkpoint", update),
+        })
+        .collect();
+    values.sort_unstable();
+    values
+}
+
+/// A checkpoint contains all committed inputs, is tagged with the number of
+/// committed transactions, and can be replayed into a new instance of the
+/// program.
+#[test]
+fn checkpoint() {
+    let (hddlog, _) = run(1);
+    commit(&hddlog, vec![insert(T1, 1), insert(T2, 2)]);
+    commit(&hddlog, vec![insert(T1, 3)]);
+
+    let path = env::temp_dir().join(format!("ddlog_checkpoint_{}.dat", process::id()));
+    assert_eq!(hddlog.checkpoint(&path), Ok(2));
+    let (version, updates) = read_checkpoint(&path);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(version, 2);
+    assert_eq!(
+        inserted_values(&updates),
+        vec![
+            ("T1".to_string(), 1),
+            ("T1".to_string(), 3),
+            ("T2".to_string(), 2)
+        ]
+    );
+    hddlog.stop().unwrap();
+
+    let (restored, _) = run(1);
+    restored.transaction_start().unwrap();
+    restored
+        .apply_updates_dynamic(&mut updates.into_iter())
+        .unwrap();
+    restored.transaction_commit().unwrap();
+    assert_eq!(table_contents(&restored, T1), vec![1, 3]);
+    assert_eq!(table_contents(&restored, T2), vec![2]);
+    assert_eq!(table_contents(&restored, T3), vec![2, 6]);
+    restored.stop().unwrap();
+}
+
+/// A checkpoint of an empty program is an empty transaction.
+#[test]
+fn checkpoint_empty() {
+    let (hddlog, _) = run(1);
+    let path = env::temp_dir().join(format!("ddlog_checkpoint_empty_{}.dat", process::id()));
+    assert_eq!(hddlog.checkpoint(&path), Ok(0));
+    let (version, updates) = read_checkpoint(&path);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(version, 0);
+    assert_eq!(updates, Vec::new());
+    hddlog.stop().unwrap();
+}
+
+/// Checkpoints taken while another thread commits transactions each contain
+/// exactly the inputs committed up to the version they are tagged with.
+#[test]
+fn checkpoint_under_load() {
+    let hddlog = Arc::new(run(2).0);
+
+    let writer = {
+        let hddlog = hddlog.clone();
+        thread::spawn(move || {
+            for x in 0..100 {
+                commit(&hddlog, vec![insert(T1, x), insert(T2, x)]);
+            }
+        })
+    };
+
+    let path = env::temp_dir().join(format!("ddlog_checkpoint_load_{}.dat", process::id()));
+    for _ in 0..20 {
+        let version = hddlog.checkpoint(&path).unwrap();
+        let (file_version, updates) = read_checkpoint(&path);
+        assert_eq!(file_version, version);
+
+        // Transaction `x` inserts `x` into `T1` and `T2`.
+        let mut expected: Vec<(String, u64)> = (0..version)
+            .flat_map(|x| vec![("T1".to_string(), x), ("T2".to_string(), x)])
+            .collect();
+        expected.sort_unstable();
+        assert_eq!(inserted_values(&updates), expected);
+    }
+
+    writer.join().unwrap();
+    fs::remove_file(&path).unwrap();
+    hddlog.stop().unwrap();
+}
+
+/// Concurrent checkpoints to the same path all succeed, and the path always
+/// contains a complete checkpoint.
+#[test]
+fn concurrent_checkpoints() {
+    let hddlog = Arc::new(run(2).0);
+    commit(&hddlog, vec![insert(T1, 1), insert(T2, 2)]);
+
+    let path = env::temp_dir().join(format!("ddlog_checkpoint_concurrent_{}.dat", process::id()));
+    let checkpointers: Vec<_> = (0..2)
+        .map(|_| {
+            let hddlog = hddlog.clone();
+            let path = path.clone();
+            thread::spawn(move || {
+                for _ in 0..20 {
+                    assert_eq!(hddlog.checkpoint(&path), Ok(1));
+                }
+            })
+        })
+        .collect();
+    for checkpointer in checkpointers {
+        checkpointer.join().unwrap();
+    }
+
+    let (version, updates) = read_checkpoint(&path);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(version, 1);
+    assert_eq!(
+        inserted_values(&updates),
+        vec![("T1".to_string(), 1), ("T2".to_string(), 2)]
+    );
+    hddlog.stop().unwrap();
+}
+
+/// A checkpoint waiting for a transaction fails instead of hanging when the
+/// program is stopped before the transaction ends.
+#[test]
+fn checkpoint_after_stop() {
+    let hddlog = Arc::new(run(1).0);
+    hddlog.transaction_start().unwrap();
+
+    let path = env::temp_dir().join(format!("ddlog_checkpoint_stop_{}.dat", process::id()));
+    let checkpointer = {
+        let hddlog = hddlog.clone();
+        let path = path.clone();
+        thread::spawn(move || hddlog.checkpoint(&path))
+    };
+
+    thread::sleep(Duration::from_millis(100));
+    hddlog.stop().unwrap();
+    assert_eq!(
+        checkpointer.join().unwrap(),
+        Err("program stopped with a transaction in progress".to_string())
+    );
+    assert!(!path.exists());
+}
+
+/// `dump_table_projected` only passes the selected fields of struct values.
+#[test]
+fn dump_table_projected() {
+    let (hddlog, _) = run(1);
+    let f3 = Q {
+        f1: true,
+        f2: test_value::String("b".to_string()),
+    };
+    let value = S::S1 {
+        f1: 1,
+        f2: test_value::String("a".to_string()),
+        f3: f3.clone(),
+        f4: Uint::default(),
+    };
+    commit(
+        &hddlog,
+        vec![Update::Insert {
+            relid: T4,
+            v: value.into_ddvalue(),
+        }],
+    );
+
+    let records = RefCell::new(Vec::new());
+    let collect = |record: &Record, _| {
+        records.borrow_mut().push(record.clone());
+        true
+    };
+    hddlog
+        .dump_table_projected(T4, Some(&["f3", "f1"]), Some(&collect))
+        .unwrap();
+    let records = records.take();
+    assert_eq!(records.len(), 1);
+
+    let fields: Vec<(&str, &Record)> = records[0]
+        .named_struct_fields()
+        .unwrap()
+        .iter()
+        .map(|(name, field)| (name.as_ref(), field))
+        .collect();
+    assert_eq!(
+        fields,
+        vec![("f1", &1u32.into_record()), ("f3", &f3.into_record())]
+    );
+
+    assert!(hddlog
+        .dump_table_projected(T4, Some(&["f1", "f5"]), Some(&collect))
+        .is_err());
+    hddlog.stop().unwrap();
+}
@@ -5,6 +5,7 @@
 #![allow(dead_code, non_snake_case, clippy::match_like_matches_macro)]
 
 use std::{
+    collections::{BTreeSet, HashMap},
     convert::TryFrom,
     io::{stdout, Write},
     net::SocketAddr,
@@ -41,6 +42,7 @@ fn handle_cmd(
     hddlog: &HDDlog,
     print_deltas: bool,
     interactive: bool,
+    relations: Option<&BTreeSet<String>>,
     upds: &mut Vec<Update<DDValue>>,
     cmd: Command,
 ) -> (Result<(), String>, bool) {
@@ -137,17 +139,22 @@ fn handle_cmd(
             Ok(())
         }
         Command::Clear(rname) => {
-            let relid = match Relations::try_from(rname.as_str()) {
-                Ok(rid) if rid.is_input() => rid as RelId,
-                _ => {
-                    let err = format!("Unknown input relation {}", rname);
-                    if interactive {
-                        eprintln!("Error: {}", err);
+            let rname = renamed_relation(hddlog, &rname);
+            if is_replayed(relations, rname) {
+                let relid = match Relations::try_from(rname) {
+                    Ok(rid) if rid.is_input() => rid as RelId,
+                    _ => {
+                        let err = format!("Unknown input relation {}", rname);
+                        if interactive {
+                            eprintln!("Error: {}", err);
+                        }
+                        return (Err(err), interactive);
                     }
-                    return (Err(err), interactive);
-                }
-            };
-            hddlog.clear_relation(relid)
+                };
+                hddlog.clear_relation(relid)
+            } else {
+                Ok(())
+            }
         }
         Command::Exit => {
             return (Ok(()), false);
@@ -161,15 +168,24 @@ fn handle_cmd(
             Ok(())
         }
         Command::Update(update, last) => {
-            match hddlog.convert_update_command(&update) {
-                Ok(update) => upds.push(update),
-                Err(err) => {
-                    upds.clear();
-                    if interactive {
-                        eprintln!("Error: {}", err);
-                    }
+            let replayed = match update.relation() {
+                RelIdentifier::RelName(rname) => {
+                    is_replayed(relations, renamed_relation(hddlog, rname))
+                }
+                RelIdentifier::RelId(_) => true,
+            };
 
-                    return (Err(err), interactive);
+            if replayed {
+                match hddlog.convert_update_command(&update) {
+                    Ok(update) => upds.push(update),
+                    Err(err) => {
+                        upds.clear();
+                        if interactive {
+                            eprintln!("Error: {}", err);
+                        }
+
+                        return (Err(err), interactive);
+                    }
                 }
             }
 
@@ -210,6 +226,20 @@ fn handle_cmd(
     }
 }
 
+/// Returns `false` if '--replay-relations' was specified and `rname` is not
+/// one of the selected relations.
+fn is_replayed(relations: Option<&BTreeSet<String>>, rname: &str) -> bool {
+    relations.map_or(true, |relations| relations.contains(rname))
+}
+
+/// Returns the name of relation `rname` after applying '--rename-relations'.
+fn renamed_relation<'a>(hddlog: &'a HDDlog, rname: &'a str) -> &'a str {
+    hddlog
+        .relation_renames()
+        .get(rname)
+        .map_or(rname, |new_name| new_name.as_ref())
+}
+
 fn dump_delta(delta: &DeltaMap<DDValue>) {
     for (table_id, table_data) in delta.iter() {
         let _ = writeln!(stdout(), "{}:", relid2name(*table_id).unwrap());
@@ -235,7 +265,11 @@ fn is_upd_cmd(c: &Command) -> bool {
     }
 }
 
-fn run(hddlog: HDDlog, print_deltas: bool) -> Result<(), String> {
+fn run(
+    hddlog: HDDlog,
+    print_deltas: bool,
+    relations: Option<BTreeSet<String>>,
+) -> Result<(), String> {
     let upds = Arc::new(Mutex::new(Vec::new()));
     let start_time = Instant::now();
     interact(|cmd, interactive| {
@@ -244,6 +278,7 @@ fn run(hddlog: HDDlog, print_deltas: bool) -> Result<(), String> {
             &hddlog,
             print_deltas,
             interactive,
+            relations.as_ref(),
             &mut upds.lock().unwrap(),
             cmd,
         )
@@ -272,6 +307,8 @@ fn main() -> Result<(), String> {
         opt differential_trace_dir:Option<String>, desc:"Path to a directory to store Differential Dataflow profiling events, e.g., './differential_trace'. Implies '--profile-differential'.";
         opt self_profiler_dir:Option<String>, desc:"Path to a directory to store self-profiler output. DDlog will create a subdirectory with name derived from process PID and store program sources and individual profiles under it. Implies '--self_profiler'. Default (if '--self-profiler' is specified) is the current working directory.";
         opt ddshow:bool=false, desc:"Start 'ddshow' profiler on sockets specified by '--timely-profiler-socket' and (optionally) '--differential-profiler-socket' options. Implies '--timely-profiler'.";
+        opt rename_relations:Option<String>, desc:"Comma-separated list of relation renames, e.g., 'OldR1=R1,OldR2=R2'. Updates to a relation under its old name are applied to the input relation with the new name, which allows replaying a session recorded before the relation was renamed.";
+        opt replay_relations:Option<String>, desc:"Comma-separated list of input relations, e.g., 'R1,R2'. Updates to all other relations are skipped, which allows replaying a subset of a recorded session. Default is to apply all updates.";
     };
     let (mut args, rest) = parser.parse_or_exit();
 
@@ -417,6 +454,16 @@ fn main() -> Result<(), String> {
         },
     };
 
+    let relations = match args.replay_relations {
+        Some(relations) => Some(parse_replay_relations(&relations)?),
+        None => None,
+    };
+
+    let renames = match args.rename_relations {
+        Some(renames) => parse_relation_renames(&renames)?,
+        None => HashMap::new(),
+    };
+
     let ddshow = if args.ddshow {
         Some(start_ddshow(
             &timely_socket.unwrap(),
@@ -428,11 +475,12 @@ fn main() -> Result<(), String> {
     };
 
     let ddlog_res = match crate::run_with_config(config, args.store) {
-        Ok((hddlog, init_output)) => {
+        Ok((mut hddlog, init_output)) => {
+            hddlog.set_relation_renames(renames);
             if args.init_snapshot {
                 dump_delta(&init_output);
             }
-            run(hddlog, args.delta)
+            run(hddlog, args.delta, relations)
         }
         Err(err) => Err(format!("Failed to run differential datalog: {}", err)),
     };
@@ -461,6 +509,49 @@ fn main() -> Result<(), String> {
     ddlog_res
 }
 
+/// Parses the argument of '--replay-relations'.
+fn parse_replay_relations(relations: &str) -> Result<BTreeSet<String>, String> {
+    relations
+        .split(',')
+        .map(str::trim)
+        .map(|relation| match Relations::try_from(relation) {
+            Ok(rid) if rid.is_input() => Ok(relation.to_string()),
+            _ => Err(format!(
+                "Invalid argument to --replay-relations: unknown input relation {}",
+                relation
+            )),
+        })
+        .collect()
+}
+
+/// Parses the argument of '--rename-relations'.
+fn parse_relation_renames(renames: &str) -> Result<HashMap<Name, Name>, String> {
+    renames
+        .split(',')
+        .map(|rename| {
+            let mut names = rename.splitn(2, '=').map(str::trim);
+            match (names.next(), names.next()) {
+                (Some(old_name), Some(new_name)) if !old_name.is_empty() => {
+                    match Relations::try_from(new_name) {
+                        Ok(rid) if rid.is_input() => Ok((
+                            Name::from(old_name.to_string()),
+                            Name::from(new_name.to_string()),
+                        )),
+                        _ => Err(format!(
+                            "Invalid relation rename '{}': unknown input relation {}",
+                            rename, new_name
+                        )),
+                    }
+                }
+                _ => Err(format!(
+                    "Invalid relation rename '{}': expected 'old_name=new_name'",
+                    rename
+                )),
+            }
+        })
+        .collect()
+}
+
 fn start_ddshow(
     timely_socket: &SocketAddr,
     differential_socket: &Option<SocketAddr>,
@@ -497,3 +588,21 @@ fn start_ddshow(
     thread::sleep(Duration::from_millis(300));
     Ok(child)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_relations_filter() {
+        assert!(is_replayed(None, "R1"));
+
+        let relations: BTreeSet<String> = vec!["R1".to_string(), "R2".to_string()]
+            .into_iter()
+            .collect();
+        assert!(is_replayed(Some(&relations), "R1"));
+        assert!(is_replayed(Some(&relations), "R2"));
+        assert!(!is_replayed(Some(&relations), "R3"));
+        assert!(!is_replayed(Some(&BTreeSet::new()), "R1"));
+    }
+}
@@ -188,34 +188,55 @@ public final class DDlogJooqProvider implements MockDataProvider {
         final MockResult[] mock = new MockResult[batchSql.length];
         int commandIndex = 0;
         synchronized (oneTxnLock) {
-            try {
-                if (trace)
-                    System.out.println("Staring transaction: " + ctx.sql());
-                ddlogHandle.transactionStart();
-                if (trace)
-                    System.out.println("Transaction started");
-                final Object[][] bindings = ctx.batchBindings();
-                for (commandIndex = 0; commandIndex < batchSql.length; commandIndex++) {
-                    final Object[] binding = bindings != null && bindings.length > commandIndex ? bindings[commandIndex] : DEFAULT_BINDING;
-                    final QueryContext context = new QueryContext(batchSql[commandIndex], binding);
-                    final SqlParser parser = SqlParser.create(batchSql[commandIndex]);
-                    final SqlNode sqlNode = parser.parseStmt();
-                    mock[commandIndex] = sqlNode.accept(new QueryVisitor(context));
+            // Primary key deletes of keys that do not exist are no-ops.  DDlog rolls back the
+            // transaction when such a delete fails, so the batch is re-executed with these
+            // statements skipped.
+            final Set<Integer> missingKeyDeletes = new HashSet<>();
+            boolean retry;
+            do {
+                retry = false;
+                try {
+                    if (trace)
+                        System.out.println("Staring transaction: " + ctx.sql());
+                    ddlogHandle.transactionStart();
+                    if (trace)
+                        System.out.println("Transaction started");
+                    final Object[][] bindings = ctx.batchBindings();
+                    for (commandIndex = 0; commandIndex < batchSql.length; commandIndex++) {
+                        final Object[] binding = bindings != null && bindings.length > commandIndex ? bindings[commandIndex] : DEFAULT_BINDING;
+                        final QueryContext context = new QueryContext(batchSql[commandIndex], binding);
+                        final SqlParser parser = SqlParser.create(batchSql[commandIndex]);
+                        final SqlNode sqlNode = parser.parseStmt();
+                        mock[commandIndex] = sqlNode.accept(
+                                new QueryVisitor(context, missingKeyDeletes.contains(commandIndex)));
+                    }
+                    ddlogHandle.transactionCommitDumpChanges(this::onChange);
+                    if (trace)
+                        System.out.println("Transaction committed");
+                } catch (final MissingKeyException e) {
+                    if (trace)
+                        System.out.println("Key to delete not found, re-executing batch: " + e.getCause().getMessage());
+                    missingKeyDeletes.add(commandIndex);
+                    retry = true;
+                } catch (final UpdateFailedException e) {
+                    // DDlog has already rolled back the transaction, including the updates of the
+                    // statements that precede the failed one, so the whole batch fails.
+                    if (trace)
+                        System.out.println("Update failed, transaction rolled back: " + e.getCause().getMessage());
+                    for (commandIndex = 0; commandIndex < batchSql.length; commandIndex++)
+                        mock[commandIndex] = exception(e.getCause());
+                } catch (final Exception e) {
+                    // We really have to catch all exceptions here to rollback, otherwise
+                    // we could be left with a started and unterminated transaction.
+                    if (trace)
+                        System.out.println("Exception: " + e.getMessage());
+                    rollback();
+                    // Not clear that this is the result for all remaining commands,
+                    // but we cannot leave these null either.
+                    for (; commandIndex < batchSql.length; commandIndex++)
+                        mock[commandIndex] = exception(e);
                 }
-                ddlogHandle.transactionCommitDumpChanges(this::onChange);
-                if (trace)
-                    System.out.println("Transaction committed");
-            } catch (final Exception e) {
-                // We really have to catch all exceptions here to rollback, otherwise
-                // we could be left with a started and unterminated transaction.
-                if (trace)
-                    System.out.println("Exception: " + e.getMessage());
-                rollback();
-                // Not clear that this is the result for all remaining commands,
-                // but we cannot leave these null either.
-                for (; commandIndex < batchSql.length; commandIndex++)
-                    mock[commandIndex] = exception(e);
-            }
+            } while (retry);
         }
         return mock;
     }
@@ -256,6 +277,18 @@ public final class DDlogJooqProvider implements MockDataProvider {
         }
     }
 
+    /*
+     * Applies a single update in the current transaction.  If the update fails, DDlog rolls
+     * back the transaction, which is reported to execute() as an UpdateFailedException.
+     */
+    private void applyUpdate(final DDlogRecCommand command) {
+        try {
+            ddlogHandle.applyUpdates(new DDlogRecCommand[]{command});
+        } catch (final DDlogException e) {
+            throw new UpdateFailedException(e);
+        }
+    }
+
     private void onChange(final DDlogCommand<DDlogRecord> command) {
         try {
             if (trace)
@@ -290,9 +323,12 @@ public final class DDlogJooqProvider implements MockDataProvider {
 
     private final class QueryVisitor extends SqlBasicVisitor<MockResult> {
         private final QueryContext context;
+        // Set when a primary key delete in this statement is known to target a missing key.
+        private final boolean keyMissing;
 
-        QueryVisitor(final QueryContext context) {
+        QueryVisitor(final QueryContext context, final boolean keyMissing) {
             this.context = context;
+            this.keyMissing = keyMissing;
         }
 
         @Override
@@ -437,13 +473,13 @@ public final class DDlogJooqProvider implements MockDataProvider {
                         recordsArray[i] = maybeOption(isNullableField, result, fi.getName());
                     }
                 }
+                final DDlogRecord record;
                 try {
-                    final DDlogRecord record = DDlogRecord.makeStruct(ddlogHandle.ddlogTableTypeName(tableName), recordsArray);
-                    final DDlogRecCommand command = new DDlogRecCommand(DDlogCommand.Kind.Insert, tableId, record);
-                    ddlogHandle.applyUpdates(new DDlogRecCommand[]{command});
+                    record = DDlogRecord.makeStruct(ddlogHandle.ddlogTableTypeName(tableName), recordsArray);
                 } catch (final DDlogException e) {
                     return exception(e);
                 }
+                applyUpdate(new DDlogRecCommand(DDlogCommand.Kind.Insert, tableId, record));
             }
             return updateCountFieldResult();
         }
@@ -460,7 +496,7 @@ public final class DDlogJooqProvider implements MockDataProvider {
             for (DDlogRecord r : clonedResults) {
                 DDlogRecCommand command = new DDlogRecCommand(DDlogCommand.Kind.DeleteVal,
                         ddlogHandle.getTableId(ddlogHandle.ddlogRelationName(tableName)), r);
-                ddlogHandle.applyUpdates(new DDlogRecCommand[]{command});
+                applyUpdate(command);
                 // Now release the cloned command
                 r.release();
             }
@@ -492,15 +528,25 @@ public final class DDlogJooqProvider implements MockDataProvider {
                 if (pkFields != null &&
                         DDlogJooqHelper.compareStringArrays(ids,
                                 pkFields.stream().map(x -> x.getUnqualifiedName().unquotedName().toString()).toArray(String[]::new))) {
+                    if (keyMissing)
+                        return emptyMockResult();
                     final DDlogRecord record = matchExpressionFromWhere(where, pkFields, context);
                     final int tableId = ddlogHandle.getTableId(ddlogHandle.ddlogRelationName(tableName));
                     final DDlogRecCommand command = new DDlogRecCommand(DDlogCommand.Kind.DeleteKey, tableId, record);
-                    ddlogHandle.applyUpdates(new DDlogRecCommand[]{command});
+                    try {
+                        applyUpdate(command);
+                    } catch (final UpdateFailedException e) {
+                        if (e.getCause().getMessage().contains("key not found"))
+                            throw new MissingKeyException(e);
+                        throw e;
+                    }
                 } else {
                     // If there are no PKs for this table, or if the PK given doesn't match the fields requested by this
                     // query, then we have to look in any indexes on the table.
                     return deleteByIndex(tableName, ids, where);
                 }
+            } catch (final UpdateFailedException e) {
+                throw e;
             } catch (final Exception e) {
                 if (e.getMessage().contains("key not found"))
                     return emptyMockResult();
@@ -548,7 +594,7 @@ public final class DDlogJooqProvider implements MockDataProvider {
                 final DDlogRecord updateRecord = DDlogRecord.makeNamedStruct("", columnsToUpdate, updatedValues);
                 final int tableId = ddlogHandle.getTableId(ddlogHandle.ddlogRelationName(tableName));
                 final DDlogRecCommand command = new DDlogRecCommand(DDlogCommand.Kind.Modify, tableId, key, updateRecord);
-                ddlogHandle.applyUpdates(new DDlogRecCommand[]{command});
+                applyUpdate(command);
             } catch (final DDlogException e) {
                 return exception(e);
             }
@@ -795,6 +841,24 @@ public final class DDlogJooqProvider implements MockDataProvider {
         }
     }
 
+    /*
+     * Thrown when DDlog rejects an update, which rolls back the current transaction.
+     */
+    private static class UpdateFailedException extends RuntimeException {
+        private UpdateFailedException(final Throwable e) {
+            super(e);
+        }
+    }
+
+    /*
+     * Thrown when a primary key delete fails because the key does not exist.
+     */
+    private static final class MissingKeyException extends UpdateFailedException {
+        private MissingKeyException(final UpdateFailedException e) {
+            super(e.getCause());
+        }
+    }
+
     private static MockResult exception(final String msg) {
         return new MockResult(new SQLException(msg));
     }
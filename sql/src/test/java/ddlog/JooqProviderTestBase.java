@@ -377,6 +377,25 @@ public abstract class JooqProviderTestBase {
         assertFalse(results.get(0).get(2, Boolean.class));
     }
 
+    /*
+     * Test that deleting a missing primary key in a batch is a no-op and does not affect
+     * the other statements of the batch
+     */
+    @Test
+    public void testDeleteMissingKeyInBatch() {
+        skipIfTestBase();
+        assert(create != null);
+        create.execute("insert into hosts values ('n1', 10, true)");
+        create.batch("insert into hosts values ('n2', 15, false)",
+                     "delete from hosts where id = 'n9'",
+                     "delete from hosts where id = 'n1'").execute();
+        final Result<Record> results = create.selectFrom(table("hostsv")).fetch();
+        assertEquals(1, results.size());
+        assertEquals("n2", results.get(0).get(0, String.class));
+        assertEquals(15, (int) results.get(0).get(1, Integer.class));
+        assertFalse(results.get(0).get(2, Boolean.class));
+    }
+
     /*
      * Test multi-row inserts
      */
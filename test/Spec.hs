@@ -207,6 +207,9 @@ parserTest fname = do
 --
 -- * If a .dat file exists for the given test, dump its content to the
 -- compiled datalog program, producing .dump and .err files
+--
+-- * If a .cli_args file exists, pass the arguments it contains to the
+-- compiled program
 compilerTest :: Bool -> FilePath -> [String] -> IO ()
 compilerTest progress file cli_args = do
     fname <- makeAbsolute file
@@ -238,7 +241,11 @@ compilerTest progress file cli_args = do
                                  "\nstderr:\n" ++ cstde ++
                                  "\n\nstdout:\n" ++ cstdo
 
-    cliTest progress fname dir cli_args
+    -- pass extra arguments to the CLI if a .cli_args file exists
+    let argsfile = replaceExtension fname "cli_args"
+    hasargs <- doesFileExist argsfile
+    file_args <- if hasargs then words <$> readFile argsfile else return []
+    cliTest progress fname dir (cli_args ++ file_args)
 
 progressThread :: IO ()
 progressThread = do